@@ -0,0 +1,70 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{save, tilemap::TileMap};
+
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Sidecar file the map is periodically written to while it has unsaved changes
+#[derive(Debug, Clone)]
+pub struct Recovery {
+    /// Map this file recovers, `None` for a map which was never saved
+    pub map_file: Option<PathBuf>,
+    pub file: PathBuf,
+}
+
+impl Recovery {
+    /// Recovery of `map_file`, next to it, or in the data directory for a map never saved
+    pub fn for_map(map_file: Option<&Path>) -> Option<Self> {
+        let file = match map_file {
+            Some(map_file) => {
+                let mut file = map_file.as_os_str().to_owned();
+                file.push(".bak");
+                file.into()
+            }
+            None => dirs::data_local_dir()?
+                .join("tilemap_editor")
+                .join("untitled.ron.bak"),
+        };
+
+        Some(Recovery {
+            map_file: map_file.map(Path::to_path_buf),
+            file,
+        })
+    }
+
+    /// Whether the recovery file exists and holds more recent changes than its map
+    pub fn is_worth_restoring(&self) -> bool {
+        let modified = |file: &Path| fs::metadata(file).and_then(|m| m.modified()).ok();
+
+        match (modified(&self.file), self.map_file.as_deref().map(modified)) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            // the map file disappeared
+            (Some(_), Some(None)) => true,
+            (Some(recovery), Some(Some(map))) => recovery > map,
+        }
+    }
+
+    pub fn write(&self, map: TileMap) -> io::Result<()> {
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        save::save_in_file(map, self.file.clone())
+    }
+
+    /// Deletes the recovery file, once its changes are saved or discarded
+    pub fn remove(&self) {
+        if self.file.exists() {
+            if let Err(err) = fs::remove_file(&self.file) {
+                eprintln!(
+                    "Could not remove the recovery file {:?}: {}",
+                    self.file, err
+                );
+            }
+        }
+    }
+}
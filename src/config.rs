@@ -0,0 +1,51 @@
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::PathBuf};
+
+/// State of the editor kept from one launch to the next
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Size of the window when it was closed
+    pub window_size: (u32, u32),
+    /// Directory of the last file opened or saved, where the file dialogs start
+    pub last_directory: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            // same as iced
+            window_size: (1024, 768),
+            last_directory: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the configuration from the config directory, using the default one if it cannot
+    /// be read
+    pub fn load() -> Self {
+        storage_file()
+            .and_then(|file| fs::read(file).ok())
+            .and_then(|content| ron::de::from_bytes(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let file = storage_file()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no configuration directory"))?;
+
+        let content = ron::ser::to_string_pretty(self, PrettyConfig::new())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(file, content)
+    }
+}
+
+fn storage_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("tilemap_editor").join("config.ron"))
+}
@@ -0,0 +1,162 @@
+//! Conversion of a map from the command line, without opening the window
+
+use std::{fs, io, path::PathBuf};
+
+use asefile::AsepriteFile;
+
+use crate::{
+    export::ExportFormat,
+    render,
+    save::{self, has_extension},
+    sheet_error,
+    tiled::Tileset,
+    tilemap::Layer,
+};
+
+/// Map to convert and where to write it, given by `--convert IN --to OUT`
+#[derive(Debug)]
+pub struct Conversion {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    /// Layer written by the CSV export
+    pub layer: Layer,
+}
+
+impl Conversion {
+    /// Writes the map in the format given by the extension of the output: one of the editor's,
+    /// a Tiled map, the layer as CSV, or an image of the map.
+    ///
+    /// Tiled maps and images need to know the size of the tiles, the one of the frames of
+    /// `tiles`, checked like the sheets opened in the editor. Tiled maps get a tileset for each
+    /// of them, and images are drawn with them.
+    pub fn run(&self, tiles: &[PathBuf]) -> io::Result<()> {
+        let map = save::load_from_file(&self.input)?;
+        if self.layer >= map.layer_count() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the map has no layer {}", self.layer),
+            ));
+        }
+
+        let output = &self.output;
+        if ["ron", "json", "tmb"]
+            .iter()
+            .any(|extension| has_extension(output, extension))
+        {
+            save::save_in_file(map, output.clone())
+        } else if has_extension(output, "tmx") {
+            let sheets = read_sheets(tiles)?;
+            let tile_size = tile_size(tiles, &sheets)?;
+            let tilesets: Vec<_> = tiles
+                .iter()
+                .zip(&sheets)
+                .map(|(file, sheet)| Tileset {
+                    name: file
+                        .file_stem()
+                        .map_or_else(String::new, |name| name.to_string_lossy().into()),
+                    tile_count: sheet.num_frames(),
+                })
+                .collect();
+            if let Some(warning) = ExportFormat::Tiled.warning(&map) {
+                eprintln!("Warning: {}", warning);
+            }
+            fs::write(
+                output,
+                ExportFormat::Tiled.export(&map, tile_size, &tilesets, self.layer),
+            )
+        } else if has_extension(output, "png") {
+            let sheets = read_sheets(tiles)?;
+            let tile_size = tile_size(tiles, &sheets)?;
+            render::write_png(&map, &sheets, tile_size, output)
+        } else if has_extension(output, "csv") {
+            // the tile size only matters to Tiled maps
+            fs::write(output, ExportFormat::Csv.export(&map, 0, &[], self.layer))
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "{:?} is not a format maps can be written to, use ron, json, tmb, tmx, csv or png",
+                    output.extension().unwrap_or_default()
+                ),
+            ))
+        }
+    }
+}
+
+/// Reads every sheet of `tiles`
+fn read_sheets(tiles: &[PathBuf]) -> io::Result<Vec<AsepriteFile>> {
+    tiles
+        .iter()
+        .map(|file| {
+            AsepriteFile::read_file(file).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("could not read {:?}: {}", file, err),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Side of the frames of `sheets`, read from `tiles`, failing if a sheet cannot be used with
+/// the others, see [`sheet_error`]
+fn tile_size(tiles: &[PathBuf], sheets: &[AsepriteFile]) -> io::Result<u16> {
+    let tile_size = match sheets.first() {
+        Some(sheet) => sheet.width() as u16,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--tiles is needed to know the size of the tiles",
+            ))
+        }
+    };
+
+    for (file, sheet) in tiles.iter().zip(sheets) {
+        if let Some(error) = sheet_error(sheet, Some(tile_size)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?} cannot be used as a tilesheet. {}", file, error),
+            ));
+        }
+    }
+    Ok(tile_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sheet of `tests/`, of 8 pixels tiles for `tile_sheet` and 16 for `large_tile_sheet`
+    fn sheet(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join(format!("{}.aseprite", name))
+    }
+
+    #[test]
+    fn sheets_of_different_sizes_are_refused() {
+        let tiles = [sheet("tile_sheet"), sheet("large_tile_sheet")];
+        let sheets = read_sheets(&tiles).unwrap();
+
+        assert_eq!(tile_size(&tiles[..1], &sheets[..1]).unwrap(), 8);
+        assert_eq!(tile_size(&tiles[1..], &sheets[1..]).unwrap(), 16);
+        let err = tile_size(&tiles, &sheets).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(
+            err.to_string().contains("16 pixels wide, instead of 8"),
+            "{err}"
+        );
+
+        // converting fails instead of drawing past the frames
+        let conversion = Conversion {
+            input: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/non_square.ron"),
+            output: std::env::temp_dir()
+                .join(format!("tilemap_editor_{}_sizes.png", std::process::id())),
+            layer: 0,
+        };
+        let mut reversed = tiles;
+        reversed.reverse();
+        assert!(conversion.run(&reversed).is_err());
+        assert!(!conversion.output.exists());
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::Display;
+
+use crate::{
+    tiled::{self, Tileset},
+    tilemap::{Layer, TileMap},
+};
+
+/// Formats the map can be exported to, without being able to save it back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Tiled,
+    /// The active layer only
+    Csv,
+    /// The custom properties of the tiles, one per line
+    PropertiesCsv,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [
+        ExportFormat::Tiled,
+        ExportFormat::Csv,
+        ExportFormat::PropertiesCsv,
+    ];
+
+    /// Name and extensions of the file dialog filter
+    pub fn filter(&self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            ExportFormat::Tiled => ("Tiled", &["tmx"]),
+            ExportFormat::Csv | ExportFormat::PropertiesCsv => ("CSV", &["csv"]),
+        }
+    }
+
+    /// Exports `map`, of which `sheets` are the tilesheets, the Tiled format writing one tileset
+    /// per sheet
+    pub fn export(
+        &self,
+        map: &TileMap,
+        tile_size: u16,
+        sheets: &[Tileset],
+        layer: Layer,
+    ) -> String {
+        match self {
+            ExportFormat::Tiled => tiled::to_tmx(map, tile_size, sheets),
+            ExportFormat::Csv => export_csv(map, layer),
+            ExportFormat::PropertiesCsv => export_properties_csv(map),
+        }
+    }
+}
+
+impl ExportFormat {
+    /// What the export of `map` loses, to warn about
+    pub fn warning(&self, map: &TileMap) -> Option<String> {
+        match self {
+            ExportFormat::Tiled => tiled::lost_details(map),
+            ExportFormat::Csv | ExportFormat::PropertiesCsv => None,
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Tiled => write!(f, "Tiled (.tmx)"),
+            ExportFormat::Csv => write!(f, "Active layer as CSV"),
+            ExportFormat::PropertiesCsv => write!(f, "Tile properties as CSV"),
+        }
+    }
+}
+
+/// One line per row of `layer`, with the value of each tile or `-1` for empty cells
+pub fn export_csv(map: &TileMap, layer: Layer) -> String {
+    let (width, height) = map.get_dimensions();
+    let mut out = String::new();
+
+    for y in 0..height {
+        let row: Vec<String> = (0..width)
+            .map(|x| {
+                map.get_layer_tile(x, y, layer)
+                    .map_or(-1, |tile| tile.value as i64)
+                    .to_string()
+            })
+            .collect();
+
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+
+    out
+}
+
+/// One line per property, as `tile,name,value`, with a header
+pub fn export_properties_csv(map: &TileMap) -> String {
+    let mut out = String::from("tile,name,value\n");
+
+    for (value, properties) in map.tile_properties() {
+        for (name, property) in properties {
+            out.push_str(&format!(
+                "{},{},{}\n",
+                value,
+                quote(name),
+                quote(&property.to_string())
+            ));
+        }
+    }
+
+    out
+}
+
+/// Quotes `field` if it holds a separator, a quote or a line break
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
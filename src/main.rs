@@ -1,44 +1,253 @@
 use iced::{
     executor,
     pure::{
-        horizontal_rule, pick_list, scrollable, vertical_rule,
-        widget::{svg::Svg, Button, Column, Row, Text},
+        checkbox, horizontal_rule, pick_list, scrollable, slider, text_input, vertical_rule,
+        widget::{svg::Svg, Button, Column, Container, Row, Text},
         Application, Element,
     },
-    Alignment, Command, Length, Settings, Space,
+    time, window, Alignment, Color, Command, Length, Point, Settings, Space, Subscription,
 };
 
+use rand::seq::SliceRandom;
+use recent::{RecentFile, RecentFiles};
 use rfd::{AsyncFileDialog, AsyncMessageDialog};
-use std::{cell::RefCell, path::PathBuf, rc::Rc};
-use tilemap::{Layer, TileMap};
+use std::{
+    cell::RefCell,
+    ffi::OsString,
+    fs, iter,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use tilemap::{Layer, LayerEntry, MapMetadata, MapTransform, PropertyValue, Tile, TileMap};
 
-use asefile::{AsepriteFile, AsepriteParseError};
+use asefile::AsepriteFile;
 
+mod autosave;
+mod config;
+mod convert;
+mod export;
 mod mapviewer;
+mod metadatadialog;
+mod minimap;
+mod offsetdialog;
+mod propertiespanel;
+mod recent;
+mod reference;
+mod render;
+mod replacedialog;
 mod save;
+mod shortcuts;
+mod statisticsdialog;
 mod style;
+mod tiled;
+mod tileimage;
 mod tilemap;
 mod tileselector;
 
-use mapviewer::{MapViewer, Tool};
-use style::SelectorTheme;
-use tilemap::Tile;
-use tileselector::TileSelector;
+use autosave::{Recovery, AUTOSAVE_INTERVAL};
+use config::Config;
+use convert::Conversion;
+use export::ExportFormat;
+use mapviewer::{MapViewer, Symmetry, Tool, ANIMATION_INTERVAL};
+use metadatadialog::{MetadataDialog, MetadataInput};
+use offsetdialog::{OffsetDialog, OffsetInput};
+use propertiespanel::PropertiesPanel;
+use reference::Reference;
+use replacedialog::{ReplaceDialog, ReplaceInput};
+use statisticsdialog::StatisticsDialog;
+use style::{SelectorTheme, Swatch};
+use tiled::Tileset;
+use tileselector::{TileBlock, TileSelector};
+
+/// Tints the brush cycles through: none, warm, cool, shaded, red and green
+const TINTS: [Option<[u8; 3]>; 6] = [
+    None,
+    Some([255, 210, 160]),
+    Some([160, 200, 255]),
+    Some([150, 150, 150]),
+    Some([255, 120, 120]),
+    Some([140, 230, 140]),
+];
 
 fn main() -> iced::Result {
-    TilemapEditor::run(Settings::default())
+    let mut flags = Flags::from_args(std::env::args_os().skip(1), Config::load());
+
+    // converting a map needs no window
+    if let Some(conversion) = flags.conversion.take() {
+        if let Err(err) = conversion.run(&flags.tiles) {
+            eprintln!("Could not convert {:?}: {}", conversion.input, err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    TilemapEditor::run(Settings {
+        window: window::Settings {
+            size: flags.config.window_size,
+            ..Default::default()
+        },
+        flags,
+        // unsaved changes are confirmed before closing
+        exit_on_close_request: false,
+        ..Default::default()
+    })
+}
+
+/// Files given on the command line, `tilemap_editor [MAP] [--tiles SHEET]...`, and the
+/// configuration read before opening the window.
+///
+/// With `--convert MAP --to FILE [--layer N]`, the map is converted instead of opened.
+#[derive(Debug, Default)]
+struct Flags {
+    map: Option<PathBuf>,
+    tiles: Vec<PathBuf>,
+    conversion: Option<Conversion>,
+    config: Config,
+}
+
+impl Flags {
+    /// Aseprite files given without `--tiles` are opened as tilesheets too
+    fn from_args(mut args: impl Iterator<Item = OsString>, config: Config) -> Self {
+        let mut flags = Flags {
+            config,
+            ..Default::default()
+        };
+
+        let (mut to, mut layer) = (None, 0);
+        while let Some(arg) = args.next() {
+            if arg == "--tiles" {
+                match args.next() {
+                    Some(sheet) => flags.tiles.push(sheet.into()),
+                    None => eprintln!("--tiles needs the path of a tilesheet"),
+                }
+                continue;
+            }
+            if arg == "--convert" {
+                match args.next() {
+                    Some(map) => flags.map = Some(map.into()),
+                    None => eprintln!("--convert needs the path of a map"),
+                }
+                continue;
+            }
+            if arg == "--to" {
+                match args.next() {
+                    Some(file) => to = Some(PathBuf::from(file)),
+                    None => eprintln!("--to needs the path of the file to write"),
+                }
+                continue;
+            }
+            if arg == "--layer" {
+                match args.next().and_then(|n| n.to_str()?.parse().ok()) {
+                    Some(n) => layer = n,
+                    None => eprintln!("--layer needs the number of a layer"),
+                }
+                continue;
+            }
+
+            let file = PathBuf::from(arg);
+            let is_sheet = file.extension().is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("ase") || ext.eq_ignore_ascii_case("aseprite")
+            });
+            if is_sheet {
+                flags.tiles.push(file);
+            } else if flags.map.is_some() {
+                eprintln!("Only one map can be opened, {:?} is ignored", file);
+            } else {
+                flags.map = Some(file);
+            }
+        }
+
+        if let Some(output) = to {
+            match flags.map.take() {
+                Some(input) => {
+                    flags.conversion = Some(Conversion {
+                        input,
+                        output,
+                        layer,
+                    })
+                }
+                None => eprintln!("--to needs a map to convert, given by --convert"),
+            }
+        }
+
+        flags
+    }
+}
+
+/// Tilesheets loaded, tiles referring to them by index
+pub type Tiles = Rc<RefCell<Vec<TileSheet>>>;
+
+pub struct TileSheet {
+    pub name: String,
+    /// File the sheet was read from, opening it again reloads the sheet
+    pub file: PathBuf,
+    pub content: AsepriteFile,
+}
+
+/// Tilesheet displayable in a pick list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SheetEntry {
+    pub sheet: u8,
+    pub name: String,
 }
 
-pub type Tiles = Rc<RefCell<Option<AsepriteFile>>>;
+impl std::fmt::Display for SheetEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Why `sheet` cannot be used next to sheets of tiles `tile_size` pixels wide, if it cannot: tiles
+/// are its frames, which must be square and of the same size in every sheet
+pub fn sheet_error(sheet: &AsepriteFile, tile_size: Option<u16>) -> Option<String> {
+    if sheet.num_frames() == 0 {
+        Some("It has no frame to use as tiles".to_string())
+    } else if sheet.width() != sheet.height() {
+        Some(format!(
+            "Its frames are {}x{} pixels, tiles must be square",
+            sheet.width(),
+            sheet.height()
+        ))
+    } else {
+        match tile_size {
+            Some(tile_size) if sheet.width() != tile_size as usize => Some(format!(
+                "Its tiles are {} pixels wide, instead of {}",
+                sheet.width(),
+                tile_size
+            )),
+            _ => None,
+        }
+    }
+}
 
 struct TilemapEditor {
-    tiles_file: Option<PathBuf>,
+    map_file: Option<PathBuf>,
+    recent_files: RecentFiles,
+    config: Config,
     loading_state: LoadingState,
     tile_selector: TileSelector,
     tiles: Tiles,
     map_viewer: MapViewer,
     horizontal_flip: bool,
     vertical_flip: bool,
+    /// Quarter turns of the brush
+    rotation: u8,
+    /// Tint of the tiles painted, one of [`TINTS`] unless picked from the map
+    tint: Option<[u8; 3]>,
+    hovered_tile: Option<(u16, u16)>,
+    /// Coordinates typed in the status bar, as `x, y`
+    jump_input: String,
+    /// Number of the tile to select, typed below the tile filter
+    tile_input: String,
+    /// File of the map the cells differing from are highlighted
+    compared_file: Option<PathBuf>,
+    offset_dialog: Option<OffsetDialog>,
+    replace_dialog: Option<ReplaceDialog>,
+    metadata_dialog: Option<MetadataDialog>,
+    statistics_dialog: Option<StatisticsDialog>,
+    properties_panel: PropertiesPanel,
+    /// Whether the window should close
+    exiting: bool,
 }
 
 enum LoadingState {
@@ -46,16 +255,17 @@ enum LoadingState {
     NewMap,
     OpeningMap,
     SavingMap,
+    ClearingMap,
     LoadingTiles,
+    OpeningReference,
+    Closing,
+    About,
     Error,
 }
 
 impl LoadingState {
     fn inactive(&self) -> bool {
-        match self {
-            LoadingState::Inactive => true,
-            _ => false,
-        }
+        matches!(self, LoadingState::Inactive)
     }
 
     fn active(&self) -> bool {
@@ -63,9 +273,22 @@ impl LoadingState {
     }
 
     fn is_error(&self) -> bool {
+        matches!(self, LoadingState::Error)
+    }
+
+    /// What the editor is waiting for, shown in the menu bar
+    fn status(&self) -> &'static str {
         match self {
-            LoadingState::Error => true,
-            _ => false,
+            LoadingState::Inactive => "",
+            LoadingState::NewMap => "Creating a new map...",
+            LoadingState::OpeningMap => "Opening...",
+            LoadingState::SavingMap => "Saving...",
+            LoadingState::ClearingMap => "Clearing...",
+            LoadingState::LoadingTiles => "Loading tiles...",
+            LoadingState::OpeningReference => "Opening reference image...",
+            LoadingState::Closing => "Closing...",
+            LoadingState::About => "About",
+            LoadingState::Error => "Error",
         }
     }
 }
@@ -73,81 +296,290 @@ impl LoadingState {
 #[derive(Debug, Clone)]
 pub enum Message {
     ErrorClosed(()), // unit type needed for command
+    ShowAbout,
+    AboutClosed(()),
+    AutosaveTick,
+    RecoveryAnswered(Option<Recovery>),
 
     // handling UI major buttons
     NewMap,
     CreateNewMap(bool),
     OpenMap,
     MapOpened(Option<PathBuf>),
+    OpenRecent(PathBuf),
+    FileDropped(PathBuf),
+    RevealMapFile,
+    WindowResized(u32, u32),
+    CloseRequested,
+    CloseConfirmed(bool),
     SaveMap,
-    MapSaved(Option<String>),
+    SaveMapAs,
+    MapSaved(Result<Option<PathBuf>, String>),
+    ExportMap(ExportFormat),
+    MapExported(Option<String>),
+    ClearLayer(Layer),
+    ClearMap,
+    TransformMap(MapTransform),
+    CropToContent,
+    SymmetryChanged(Symmetry),
+    AllLayersChanged(bool),
+    LayersCleared(Vec<Layer>),
 
     // tiles selector events
     OpenTiles,
+    SheetSelected(u8),
     TilesOpened(Option<PathBuf>),
     TileSelected(u32),
+    SheetTileSelected(u8, u32),
+    TileToggled(u32),
+    BlockSelected(u32, u32),
     TileUnSelected,
+    AnimationSelected(u32),
+    TileFilterChanged(String),
+    ToggleTileIndices(bool),
+    SetSelectorColumns(u32),
 
     // Toolbar events
     ToolSelected(Tool),
     HorizontalFlip(bool),
     VerticalFlip(bool),
+    RotateBrush,
+    CycleTint,
+    SetBrushSize(u16),
+    /// Grows the brush, or shrinks it for a negative step
+    ChangeBrushSize(i32),
     LayerChanged(Layer),
+    SetLayerOpacity(Layer, f32),
+    AddLayer,
+    RemoveLayer(Layer),
+    LayerRemoved(Option<Layer>),
+    /// Moves the active layer up if `true`, down otherwise
+    MoveLayer(bool),
+    CopyLayer(Layer, Layer),
+    SwapLayers(Layer, Layer),
+    FillLayer,
+    OffsetDialogOpened,
+    OffsetDialogClosed,
+    OffsetInput(OffsetInput),
+    OffsetLayer(i32, i32, bool),
+    ReplaceDialogOpened,
+    ReplaceDialogClosed,
+    ReplaceInput(ReplaceInput),
+    ReplaceTile((u8, u32), (u8, u32), Option<Layer>),
+    StatisticsDialogOpened,
+    StatisticsDialogClosed,
+    StatisticsWholeMap(bool),
+    MetadataDialogOpened,
+    MetadataDialogClosed,
+    MetadataInput(MetadataInput),
+    MetadataChanged(MapMetadata),
+    PropertyNameChanged(String),
+    TilePropertyChanged(u32, String, Option<PropertyValue>),
+    TilePropertyAdded(u32, PropertyValue),
+    ToggleGrid,
+    ToggleCheckerboard,
+    SetChunkSize(Option<u16>),
+    FitView,
+    ZoomMap(f32, Point),
+    JumpTo(u16, u16),
+    JumpInputChanged(String),
+    TileInputChanged(String),
+    TileNumberSubmitted(u32),
+    ResetView,
+    CycleEmptyColour,
 
     // map viewer events
     Redraw,
+    AnimationTick,
+    CursorMovedTile(u16, u16),
+    CursorLeftMap,
     PaintTile(u16, u16),
     ClearTile(u16, u16),
+    PickTile(u16, u16),
+    SelectRegion(u16, u16),
+    SelectMatching(u16, u16),
+    SelectEnclosed(u16, u16),
+    OpenReference,
+    ReferenceOpened(Option<PathBuf>),
+    ReferenceClosed,
+    ReferenceShown(bool),
+    ReferenceSnapped(bool),
+    SetReferenceOpacity(f32),
+    SetReferenceScale(f32),
+    MoveReference(i32, i32),
+    CompareMap,
+    ComparedMapOpened(Option<PathBuf>),
+    ComparisonClosed,
+    SelectRect(u16, u16, i32, i32),
+    CopySelection,
+    StampAt(i32, i32),
+    /// Left to right if true, top to bottom otherwise
+    MirrorClipboard(bool),
+    SelectAll,
+    InvertSelection,
+    MoveSelection(i32, i32),
+    /// Moves the selection by a cell in each direction, or by a chunk if `true`
+    NudgeSelection(i32, i32, bool),
+    DeleteSelection,
+    FillSelection,
+    Deselect,
     RectStarted,
     PaintRect(u16, u16, i32, i32),
+    PaintRectOutline(u16, u16, i32, i32),
+    PaintEllipse(u16, u16, i32, i32),
 }
 
 impl Application for TilemapEditor {
     type Message = Message;
     type Executor = executor::Default;
-    type Flags = ();
+    type Flags = Flags;
 
-    fn new(_flags: Self::Flags) -> (Self, Command<Message>) {
+    fn new(flags: Self::Flags) -> (Self, Command<Message>) {
         println!(env!("CARGO_MANIFEST_DIR"));
-        let tiles = Rc::new(RefCell::new(None));
+        let tiles = Rc::new(RefCell::new(Vec::new()));
+        let recent_files = RecentFiles::load();
+
+        // changes of the last map worked on, or of a map never saved, may have been lost in a crash
+        let recovery = [recent_files.latest(), None]
+            .into_iter()
+            .filter_map(Recovery::for_map)
+            .find(Recovery::is_worth_restoring);
+
+        let (loading_state, command) = match (flags.map, recovery) {
+            // the map asked for is opened rather than offering a recovery
+            (Some(map), _) => (
+                LoadingState::OpeningMap,
+                Command::perform(async { Some(map) }, Message::MapOpened),
+            ),
+            (None, Some(recovery)) => (
+                LoadingState::OpeningMap,
+                Command::perform(Self::offer_recovery(recovery), Message::RecoveryAnswered),
+            ),
+            (None, None) => (LoadingState::Inactive, Command::none()),
+        };
+        let command = Command::batch(
+            iter::once(command).chain(
+                flags
+                    .tiles
+                    .into_iter()
+                    .map(|sheet| Command::perform(async { Some(sheet) }, Message::TilesOpened)),
+            ),
+        );
+
         (
             TilemapEditor {
-                tiles_file: None,
-                loading_state: LoadingState::Inactive,
+                map_file: None,
+                recent_files,
+                config: flags.config,
+                loading_state,
                 tile_selector: TileSelector::new(tiles.clone()),
                 map_viewer: MapViewer::new(tiles.clone()),
                 tiles,
                 horizontal_flip: false,
                 vertical_flip: false,
+                rotation: 0,
+                tint: None,
+                hovered_tile: None,
+                jump_input: String::new(),
+                tile_input: String::new(),
+                compared_file: None,
+                offset_dialog: None,
+                replace_dialog: None,
+                metadata_dialog: None,
+                statistics_dialog: None,
+                properties_panel: Default::default(),
+                exiting: false,
             },
-            Command::none(),
+            command,
         )
     }
 
+    fn should_exit(&self) -> bool {
+        self.exiting
+    }
+
     fn title(&self) -> String {
-        "Tilemap editor".to_string()
+        let mut title = "Tilemap editor".to_string();
+
+        let name = &self.map_viewer.metadata().name;
+        let file_name = self.map_file.as_ref().and_then(|file| file.file_name());
+        match (name.is_empty(), file_name) {
+            (false, Some(file_name)) => {
+                title = format!("{} - {} ({})", title, name, file_name.to_string_lossy())
+            }
+            (false, None) => title = format!("{} - {}", title, name),
+            (true, Some(file_name)) => {
+                title = format!("{} - {}", title, file_name.to_string_lossy())
+            }
+            (true, None) => {}
+        }
+
+        if self.map_viewer.modified {
+            title.push_str(" *");
+        }
+
+        title
     }
 
     fn view(&self) -> Element<'_, Self::Message> {
         let tool_button = |intended, img| {
-            Button::new(load_svg(img))
-                .style(SelectorTheme::pick(self.map_viewer.tool, intended))
-                .on_press(Message::ToolSelected(intended))
+            let button = Button::new(load_svg(img))
+                .style(SelectorTheme::pick(self.map_viewer.tool, intended));
+
+            // no tool change while a dialog is open
+            if self.loading_state.active() {
+                button
+            } else {
+                button.on_press(Message::ToolSelected(intended))
+            }
         };
 
         let check_button = |value, img, f: fn(bool) -> Message| {
-            Button::new(load_svg(img))
-                .style(SelectorTheme::pick(value, true))
-                .on_press(f(!value))
+            let button = Button::new(load_svg(img)).style(SelectorTheme::pick(value, true));
+
+            // the brush is not changed while a dialog is open either
+            if self.loading_state.active() {
+                button
+            } else {
+                button.on_press(f(!value))
+            }
         };
 
         Column::new()
             // menu bar
             .push(
                 Row::new()
-                    .push(Button::new(Text::new("New")).on_press(Message::NewMap))
-                    .push(Button::new(Text::new("Open")).on_press(Message::OpenMap))
-                    .push(Button::new(Text::new("Save")).on_press(Message::SaveMap)),
+                    .push(self.menu_button("New", Some(Message::NewMap)))
+                    .push(self.menu_button("Open", Some(Message::OpenMap)))
+                    .push(
+                        pick_list(self.recent_files.entries(), None, |file: RecentFile| {
+                            Message::OpenRecent(file.0)
+                        })
+                        .placeholder("Recent"),
+                    )
+                    .push(self.menu_button("Save", Some(Message::SaveMap)))
+                    .push(self.menu_button("Save As", Some(Message::SaveMapAs)))
+                    .push(self.menu_button(
+                        "Show in folder",
+                        // a map never saved is nowhere
+                        self.map_file.as_ref().map(|_| Message::RevealMapFile),
+                    ))
+                    .push(
+                        pick_list(&ExportFormat::ALL[..], None, Message::ExportMap)
+                            .placeholder("Export"),
+                    )
+                    .push(
+                        pick_list(&MapTransform::ALL[..], None, Message::TransformMap)
+                            .placeholder("Transform"),
+                    )
+                    .push(self.menu_button("Crop", Some(Message::CropToContent)))
+                    .push(self.menu_button("Properties", Some(Message::MetadataDialogOpened)))
+                    .push(self.menu_button("Reference", Some(Message::OpenReference)))
+                    .push(self.menu_button("Compare", Some(Message::CompareMap)))
+                    .push(self.menu_button("Clear map", Some(Message::ClearMap)))
+                    .push(self.menu_button("About", Some(Message::ShowAbout)))
+                    .push(Space::new(Length::Fill, Length::Shrink))
+                    .push(Text::new(self.loading_state.status())),
             )
             .push(horizontal_rule(2))
             // window content
@@ -158,12 +590,56 @@ impl Application for TilemapEditor {
                         Column::new()
                             .align_items(Alignment::Center)
                             .width(Length::Units(200))
-                            .push(Text::new(match &self.tiles_file {
-                                Some(path) => path.file_name().unwrap().to_str().unwrap(),
-                                None => "No file selected",
-                            }))
+                            .push(self.sheet_tabs())
+                            .push(text_input(
+                                "Filter by group",
+                                self.tile_selector.filter(),
+                                Message::TileFilterChanged,
+                            ))
+                            .push(self.tile_input())
+                            .push(self.tile_selector.recent_view(200))
+                            .push(
+                                Row::new()
+                                    .spacing(5)
+                                    .align_items(Alignment::Center)
+                                    .push(checkbox(
+                                        "Show indices",
+                                        self.tile_selector.show_indices(),
+                                        Message::ToggleTileIndices,
+                                    ))
+                                    .push(Text::new(format!(
+                                        "Columns: {}",
+                                        self.tile_selector.tiles_per_line()
+                                    )))
+                                    .push(Button::new(Text::new("-")).on_press(
+                                        Message::SetSelectorColumns(
+                                            self.tile_selector.tiles_per_line().saturating_sub(1),
+                                        ),
+                                    ))
+                                    .push(Button::new(Text::new("+")).on_press(
+                                        Message::SetSelectorColumns(
+                                            self.tile_selector.tiles_per_line() + 1,
+                                        ),
+                                    )),
+                            )
                             .push(scrollable(self.tile_selector.view()).height(Length::Fill))
-                            .push(Button::new("Open tiles").on_press(Message::OpenTiles)),
+                            .push(
+                                Row::new()
+                                    .spacing(5)
+                                    .push(self.menu_button("Add tiles", Some(Message::OpenTiles)))
+                                    .push(
+                                        self.menu_button(
+                                            "Reload tiles",
+                                            // opening the file of a sheet again reloads it
+                                            self.tiles
+                                                .borrow()
+                                                .get(self.tile_selector.sheet() as usize)
+                                                .map(|sheet| {
+                                                    Message::TilesOpened(Some(sheet.file.clone()))
+                                                }),
+                                        ),
+                                    ),
+                            ),
                     )
                     .push(vertical_rule(2))
                     .push(
@@ -173,7 +649,14 @@ impl Application for TilemapEditor {
                                 Row::new()
                                     .push(tool_button(Tool::Pen, "pencil.svg"))
                                     .push(tool_button(Tool::Rect, "rectangle.svg"))
+                                    .push(tool_button(Tool::RectOutline, "rectangle_outline.svg"))
+                                    .push(tool_button(Tool::Ellipse, "ellipse.svg"))
                                     .push(tool_button(Tool::Selection, "selection.svg"))
+                                    .push(tool_button(Tool::Picker, "picker.svg"))
+                                    .push(tool_button(Tool::MagicWand, "wand.svg"))
+                                    .push(tool_button(Tool::Stamp, "stamp.svg"))
+                                    .push(tool_button(Tool::Scatter, "scatter.svg"))
+                                    .push(tool_button(Tool::Eraser, "eraser.svg"))
                                     .push(Space::new(Length::Fill, Length::Shrink))
                                     .push(check_button(
                                         self.horizontal_flip,
@@ -185,35 +668,232 @@ impl Application for TilemapEditor {
                                         "vertical_flip.svg",
                                         Message::VerticalFlip,
                                     ))
-                                    .push(Space::new(Length::Fill, Length::Shrink))
+                                    .push(
+                                        Button::new(Text::new(format!(
+                                            "{}°",
+                                            self.rotation as u32 * 90
+                                        )))
+                                        .on_press(Message::RotateBrush),
+                                    )
+                                    .push(
+                                        Button::new(
+                                            Container::new(Space::new(
+                                                Length::Units(20),
+                                                Length::Units(20),
+                                            ))
+                                            .style(
+                                                Swatch(match self.tint {
+                                                    Some([r, g, b]) => Color::from_rgb8(r, g, b),
+                                                    None => Color::WHITE,
+                                                }),
+                                            ),
+                                        )
+                                        .on_press(Message::CycleTint),
+                                    )
+                                    .push(self.tile_selector.brush_preview(self.brush()))
+                                    .push(Text::new(format!("{0}x{0}", self.map_viewer.brush_size)))
+                                    .push(Button::new(Text::new("-")).on_press(
+                                        Message::SetBrushSize(
+                                            self.map_viewer.brush_size.saturating_sub(1),
+                                        ),
+                                    ))
+                                    .push(Button::new(Text::new("+")).on_press(
+                                        Message::SetBrushSize(self.map_viewer.brush_size + 1),
+                                    ))
                                     .push(pick_list(
-                                        &Layer::ALL[..],
-                                        Some(self.map_viewer.layer),
-                                        Message::LayerChanged,
+                                        &Symmetry::ALL[..],
+                                        Some(self.map_viewer.symmetry),
+                                        Message::SymmetryChanged,
+                                    ))
+                                    .push(Space::new(Length::Fill, Length::Shrink))
+                                    .push(
+                                        Button::new(load_svg("grid.svg"))
+                                            .style(SelectorTheme::pick(
+                                                self.map_viewer.show_grid,
+                                                true,
+                                            ))
+                                            .on_press(Message::ToggleGrid),
+                                    )
+                                    .push(Text::new(match self.map_viewer.chunk_size {
+                                        Some(chunk_size) => format!("Chunks: {}", chunk_size),
+                                        None => "Chunks: off".to_string(),
+                                    }))
+                                    .push(Button::new(Text::new("-")).on_press(
+                                        Message::SetChunkSize(
+                                            self.map_viewer.chunk_size.map(|size| size - 1),
+                                        ),
+                                    ))
+                                    .push(Button::new(Text::new("+")).on_press(
+                                        Message::SetChunkSize(Some(
+                                            self.map_viewer.chunk_size.map_or(2, |size| size + 1),
+                                        )),
+                                    ))
+                                    .push(
+                                        Button::new(
+                                            Container::new(Space::new(
+                                                Length::Units(20),
+                                                Length::Units(20),
+                                            ))
+                                            .style(Swatch(self.map_viewer.empty_colour)),
+                                        )
+                                        .on_press(Message::CycleEmptyColour),
+                                    )
+                                    .push(
+                                        Button::new(load_svg("checkerboard.svg"))
+                                            .style(SelectorTheme::pick(
+                                                self.map_viewer.checkerboard,
+                                                true,
+                                            ))
+                                            .on_press(Message::ToggleCheckerboard),
+                                    )
+                                    .push(Button::new(Text::new("Fit")).on_press(Message::FitView))
+                                    .push(
+                                        Button::new(Text::new("100%")).on_press(Message::ResetView),
+                                    )
+                                    .push(self.menu_button(
+                                        "Fill layer",
+                                        // nothing to fill with
+                                        self.brush().map(|_| Message::FillLayer),
+                                    ))
+                                    .push(
+                                        self.menu_button(
+                                            "Offset",
+                                            Some(Message::OffsetDialogOpened),
+                                        ),
+                                    )
+                                    .push(
+                                        self.menu_button(
+                                            "Replace",
+                                            Some(Message::ReplaceDialogOpened),
+                                        ),
+                                    )
+                                    .push(self.menu_button(
+                                        "Usage",
+                                        Some(Message::StatisticsDialogOpened),
+                                    ))
+                                    .push(self.menu_button(
+                                        "Clear layer",
+                                        Some(Message::ClearLayer(self.map_viewer.layer)),
                                     )),
                             )
+                            .push(match &self.offset_dialog {
+                                Some(dialog) => dialog.view(),
+                                None => Space::new(Length::Shrink, Length::Shrink).into(),
+                            })
+                            .push(match &self.replace_dialog {
+                                Some(dialog) => {
+                                    dialog.view(self.map_viewer.layer, self.sheet_entries())
+                                }
+                                None => Space::new(Length::Shrink, Length::Shrink).into(),
+                            })
+                            .push(match &self.metadata_dialog {
+                                Some(dialog) => dialog.view(),
+                                None => Space::new(Length::Shrink, Length::Shrink).into(),
+                            })
+                            .push(match self.map_viewer.reference() {
+                                Some(reference) => reference.view(self.map_viewer.tile_size),
+                                None => Space::new(Length::Shrink, Length::Shrink).into(),
+                            })
+                            .push(self.comparison_view())
+                            .push(match &self.statistics_dialog {
+                                Some(dialog) => dialog.view(
+                                    self.map_viewer.tile_counts(
+                                        (!dialog.whole_map()).then_some(self.map_viewer.layer),
+                                    ),
+                                    &self.tile_selector,
+                                    self.sheet_entries(),
+                                ),
+                                None => Space::new(Length::Shrink, Length::Shrink).into(),
+                            })
                             .push(Space::new(Length::Fill, Length::Units(3)))
                             .push(self.map_viewer.view()),
-                    ),
+                    )
+                    .push(vertical_rule(2))
+                    .push(self.layer_panel()),
+            )
+            .push(horizontal_rule(2))
+            // status bar
+            .push(
+                Row::new()
+                    .spacing(20)
+                    .push(Text::new(match self.hovered_tile {
+                        Some((x, y)) => format!("({}, {})", x, y),
+                        None => "--".to_string(),
+                    }))
+                    .push(Text::new(format!(
+                        "Layer: {}",
+                        self.map_viewer.layer_name(self.map_viewer.layer)
+                    )))
+                    .push(Text::new(match self.tile_selector.get_selected() {
+                        Some(tile) => format!("Tile: {}", tile),
+                        None => "Tile: none".to_string(),
+                    }))
+                    .push(self.jump_input()),
             )
             .into()
     }
 
     fn update(&mut self, message: Self::Message) -> Command<Message> {
         if self.loading_state.is_error() {
-            match message {
-                Message::ErrorClosed(_) => self.loading_state = LoadingState::Inactive,
-                _ => {}
+            if let Message::ErrorClosed(_) = message {
+                self.loading_state = LoadingState::Inactive
             }
             return Command::none();
         }
 
         match message {
-            Message::ErrorClosed(_) => {
+            // an error shown while another operation was pending leaves it pending
+            Message::ErrorClosed(_) => {}
+            Message::ShowAbout => {
+                if self.loading_state.active() {
+                    return Command::none();
+                }
+
+                self.loading_state = LoadingState::About;
+                return Command::perform(Self::show_about(), Message::AboutClosed);
+            }
+            Message::AboutClosed(_) => {
+                self.loading_state = LoadingState::Inactive;
+            }
+
+            Message::AutosaveTick => {
+                if self.map_viewer.modified {
+                    if let Some(recovery) = Recovery::for_map(self.map_file.as_deref()) {
+                        if let Err(err) = recovery.write(self.map_viewer.get_map_instant()) {
+                            eprintln!("Could not write the recovery file: {}", err);
+                        }
+                    }
+                }
+            }
+
+            Message::RecoveryAnswered(recovery) => {
                 self.loading_state = LoadingState::Inactive;
+
+                if let Some(recovery) = recovery {
+                    match save::load_from_file(&recovery.file) {
+                        Ok(map) => {
+                            self.metadata_dialog = None;
+                            self.map_viewer.set_entire_map(map);
+                            // the recovered changes are not saved in the map file yet
+                            self.map_viewer.modified = true;
+                            self.map_file = recovery.map_file;
+                        }
+                        Err(err) => {
+                            self.loading_state = LoadingState::Error;
+                            return Command::perform(
+                                Self::error_opening_map(recovery.file, err.to_string()),
+                                Message::ErrorClosed,
+                            );
+                        }
+                    }
+                }
             }
 
             Message::NewMap => {
+                if self.loading_state.active() {
+                    return Command::none();
+                }
+
                 self.loading_state = LoadingState::NewMap;
 
                 return Command::perform(
@@ -226,6 +906,9 @@ impl Application for TilemapEditor {
                 self.loading_state = LoadingState::Inactive;
 
                 if create {
+                    self.discard_recovery();
+                    self.map_file = None;
+                    self.metadata_dialog = None;
                     self.map_viewer.set_entire_map(TileMap::new(32, 32))
                 }
             }
@@ -238,29 +921,152 @@ impl Application for TilemapEditor {
                 self.loading_state = LoadingState::OpeningMap;
 
                 return Command::perform(
-                    Self::open_map(self.map_viewer.modified),
+                    Self::open_map(self.map_viewer.modified, self.config.last_directory.clone()),
                     Message::MapOpened,
                 );
             }
 
             Message::MapOpened(new_map_file) => {
                 self.loading_state = LoadingState::Inactive;
-                match new_map_file {
-                    Some(new_map_file) => {
-                        let new_map = save::load_from_file(&new_map_file);
+                if let Some(new_map_file) = new_map_file {
+                    let new_map = save::load_from_file(&new_map_file);
 
-                        match new_map {
-                            Ok(new_map) => self.map_viewer.set_entire_map(new_map),
-                            Err(err) => {
-                                self.loading_state = LoadingState::Error;
-                                return Command::perform(
-                                    Self::error_opening_map(new_map_file, err.to_string()),
-                                    Message::ErrorClosed,
-                                );
-                            }
+                    match new_map {
+                        Ok(new_map) => {
+                            self.discard_recovery();
+                            self.remember_file(new_map_file.clone());
+                            // saving an imported map must not overwrite the original
+                            self.map_file = if save::is_imported(&new_map_file) {
+                                None
+                            } else {
+                                Some(new_map_file)
+                            };
+                            // the properties edited are the ones of the previous map
+                            self.metadata_dialog = None;
+                            self.map_viewer.set_entire_map(new_map)
                         }
+                        Err(err) => {
+                            self.loading_state = LoadingState::Error;
+                            return Command::perform(
+                                Self::error_opening_map(new_map_file, err.to_string()),
+                                Message::ErrorClosed,
+                            );
+                        }
+                    }
+                }
+            }
+
+            Message::OpenRecent(file) => {
+                if self.loading_state.active() {
+                    return Command::none();
+                }
+
+                if !file.exists() {
+                    self.recent_files.remove(&file);
+                    self.save_recent_files();
+
+                    self.loading_state = LoadingState::Error;
+                    return Command::perform(
+                        Self::error_opening_map(
+                            file,
+                            "The file does not exist anymore".to_string(),
+                        ),
+                        Message::ErrorClosed,
+                    );
+                }
+
+                self.loading_state = LoadingState::OpeningMap;
+
+                return Command::perform(
+                    Self::open_recent(self.map_viewer.modified, file),
+                    Message::MapOpened,
+                );
+            }
+
+            Message::CloseRequested => {
+                if self.loading_state.active() {
+                    return Command::none();
+                }
+
+                if self.map_viewer.modified {
+                    self.loading_state = LoadingState::Closing;
+                    return Command::perform(confirm_close(), Message::CloseConfirmed);
+                }
+                self.exit();
+            }
+            Message::CloseConfirmed(close) => {
+                self.loading_state = LoadingState::Inactive;
+                if close {
+                    // the changes were discarded on purpose
+                    self.discard_recovery();
+                    self.exit();
+                }
+            }
+            Message::WindowResized(width, height) => self.config.window_size = (width, height),
+            Message::RevealMapFile => {
+                let directory = self.map_file.as_deref().and_then(Path::parent);
+                if let Some(directory) = directory {
+                    // a file without directory is in the working one
+                    let directory = if directory.as_os_str().is_empty() {
+                        Path::new(".")
+                    } else {
+                        directory
+                    };
+
+                    if let Err(err) = opener::open(directory) {
+                        self.loading_state = LoadingState::Error;
+                        return Command::perform(
+                            Self::error_opening_map(directory.to_path_buf(), err.to_string()),
+                            Message::ErrorClosed,
+                        );
+                    }
+                }
+            }
+
+            Message::FileDropped(file) => {
+                if self.loading_state.active() {
+                    return Command::none();
+                }
+
+                let extension = file
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+
+                match extension.as_str() {
+                    "ron" | "json" | "tmb" | "tmx" => {
+                        self.loading_state = LoadingState::OpeningMap;
+
+                        return Command::perform(
+                            Self::open_recent(self.map_viewer.modified, file),
+                            Message::MapOpened,
+                        );
+                    }
+                    "ase" | "aseprite" => return self.update(Message::TilesOpened(Some(file))),
+                    // tilesheets are the frames of an Aseprite file, which a plain image has not
+                    "png" => {
+                        self.loading_state = LoadingState::Error;
+                        return Command::perform(
+                            Self::error_opening_map(
+                                file,
+                                "PNG images cannot be used as tilesheets, which are read frame by \
+                                frame from Aseprite files: import the image in Aseprite and save \
+                                it as a .aseprite file"
+                                    .to_string(),
+                            ),
+                            Message::ErrorClosed,
+                        );
+                    }
+                    _ => {
+                        self.loading_state = LoadingState::Error;
+                        return Command::perform(
+                            Self::error_opening_map(
+                                file,
+                                "Only maps and Aseprite files can be opened".to_string(),
+                            ),
+                            Message::ErrorClosed,
+                        );
                     }
-                    None => {}
                 }
             }
 
@@ -272,16 +1078,45 @@ impl Application for TilemapEditor {
                 self.loading_state = LoadingState::SavingMap;
 
                 return Command::perform(
-                    Self::save_map(self.map_viewer.get_map_instant()),
+                    Self::save_map(
+                        self.map_viewer.get_map_instant(),
+                        self.map_file.clone(),
+                        self.map_file.clone(),
+                        self.config.last_directory.clone(),
+                    ),
+                    Message::MapSaved,
+                );
+            }
+            Message::SaveMapAs => {
+                if self.loading_state.active() {
+                    return Command::none();
+                }
+
+                self.loading_state = LoadingState::SavingMap;
+
+                return Command::perform(
+                    Self::save_map(
+                        self.map_viewer.get_map_instant(),
+                        None,
+                        self.map_file.clone(),
+                        self.config.last_directory.clone(),
+                    ),
                     Message::MapSaved,
                 );
             }
-            Message::MapSaved(potential_error) => match potential_error {
-                None => {
+            Message::MapSaved(result) => match result {
+                Ok(saved_file) => {
                     self.loading_state = LoadingState::Inactive;
-                    self.map_viewer.modified = false;
+
+                    // nothing happens if the dialog was cancelled
+                    if let Some(saved_file) = saved_file {
+                        self.discard_recovery();
+                        self.remember_file(saved_file.clone());
+                        self.map_file = Some(saved_file);
+                        self.map_viewer.modified = false;
+                    }
                 }
-                Some(error_message) => {
+                Err(error_message) => {
                     self.loading_state = LoadingState::Error;
                     return Command::perform(
                         Self::error_with_save(error_message),
@@ -290,6 +1125,149 @@ impl Application for TilemapEditor {
                 }
             },
 
+            Message::ExportMap(format) => {
+                if self.loading_state.active() {
+                    return Command::none();
+                }
+
+                self.loading_state = LoadingState::SavingMap;
+
+                let sheets: Vec<_> = self
+                    .tiles
+                    .borrow()
+                    .iter()
+                    .map(|sheet| Tileset {
+                        name: sheet.name.clone(),
+                        tile_count: sheet.content.num_frames(),
+                    })
+                    .collect();
+                let map = self.map_viewer.get_map_instant();
+                let content = format.export(
+                    &map,
+                    self.map_viewer.tile_size,
+                    &sheets,
+                    self.map_viewer.layer,
+                );
+                return Command::perform(
+                    Self::export_map(
+                        content,
+                        format,
+                        format.warning(&map),
+                        self.config.last_directory.clone(),
+                    ),
+                    Message::MapExported,
+                );
+            }
+            Message::MapExported(potential_error) => {
+                self.loading_state = LoadingState::Inactive;
+
+                if let Some(error_message) = potential_error {
+                    self.loading_state = LoadingState::Error;
+                    return Command::perform(
+                        Self::error_with_save(error_message),
+                        Message::ErrorClosed,
+                    );
+                }
+            }
+
+            Message::ClearLayer(layer) => {
+                if self.loading_state.active() {
+                    return Command::none();
+                }
+                self.loading_state = LoadingState::ClearingMap;
+
+                let description = format!(
+                    "Every tile of the {} layer will be removed.",
+                    self.map_viewer.layer_name(layer)
+                );
+                return Command::perform(
+                    Self::confirm_clear(vec![layer], description),
+                    Message::LayersCleared,
+                );
+            }
+            Message::ClearMap => {
+                if self.loading_state.active() {
+                    return Command::none();
+                }
+                self.loading_state = LoadingState::ClearingMap;
+
+                let layers = self.map_viewer.layers().iter().map(|l| l.layer).collect();
+                return Command::perform(
+                    Self::confirm_clear(
+                        layers,
+                        "Every tile of the map will be removed.".to_string(),
+                    ),
+                    Message::LayersCleared,
+                );
+            }
+            // nothing is edited while a dialog is open: a map being saved must stay the one
+            // written. The lists, shortcuts and canvas sending these cannot be disabled, like
+            // buttons are.
+            Message::TransformMap(..)
+            | Message::CropToContent
+            | Message::SymmetryChanged(..)
+            | Message::AllLayersChanged(..)
+            | Message::ToolSelected(..)
+            | Message::HorizontalFlip(..)
+            | Message::VerticalFlip(..)
+            | Message::RotateBrush
+            | Message::CycleTint
+            | Message::SetBrushSize(..)
+            | Message::ChangeBrushSize(..)
+            | Message::LayerChanged(..)
+            | Message::SetLayerOpacity(..)
+            | Message::AddLayer
+            | Message::MoveLayer(..)
+            | Message::CopyLayer(..)
+            | Message::SwapLayers(..)
+            | Message::FillLayer
+            | Message::OffsetDialogOpened
+            | Message::OffsetLayer(..)
+            | Message::ReplaceDialogOpened
+            | Message::ReplaceTile(..)
+            | Message::StatisticsDialogOpened
+            | Message::MetadataDialogOpened
+            | Message::MetadataChanged(..)
+            | Message::TilePropertyChanged(..)
+            | Message::TilePropertyAdded(..)
+            | Message::ToggleGrid
+            | Message::ToggleCheckerboard
+            | Message::SetChunkSize(..)
+            | Message::CycleEmptyColour
+            | Message::PaintTile(..)
+            | Message::ClearTile(..)
+            | Message::PickTile(..)
+            | Message::SelectRegion(..)
+            | Message::SelectMatching(..)
+            | Message::SelectEnclosed(..)
+            | Message::SelectRect(..)
+            | Message::CopySelection
+            | Message::StampAt(..)
+            | Message::MirrorClipboard(..)
+            | Message::SelectAll
+            | Message::InvertSelection
+            | Message::MoveSelection(..)
+            | Message::NudgeSelection(..)
+            | Message::DeleteSelection
+            | Message::FillSelection
+            | Message::Deselect
+            | Message::RectStarted
+            | Message::PaintRect(..)
+            | Message::PaintRectOutline(..)
+            | Message::PaintEllipse(..)
+                if self.loading_state.active() => {}
+            Message::TransformMap(transform) => self.map_viewer.transform_map(transform),
+            Message::CropToContent => self.map_viewer.crop_to_content(),
+            Message::SymmetryChanged(symmetry) => self.map_viewer.symmetry = symmetry,
+            Message::AllLayersChanged(all_layers) => self.map_viewer.all_layers = all_layers,
+            Message::LayersCleared(layers) => {
+                self.loading_state = LoadingState::Inactive;
+
+                for layer in layers {
+                    self.map_viewer.clear_layer(layer);
+                }
+            }
+
             Message::OpenTiles => {
                 if self.loading_state.active() {
                     return Command::none();
@@ -297,28 +1275,83 @@ impl Application for TilemapEditor {
                 self.loading_state = LoadingState::LoadingTiles;
 
                 return Command::perform(
-                    Self::open_tiles(self.tiles_file.is_some()),
+                    Self::open_tiles(self.config.last_directory.clone()),
                     Message::TilesOpened,
                 );
             }
 
+            // sheets given on the command line, dropped or reloaded open while another operation
+            // may be pending, such as the recovery prompt, which goes on
             Message::TilesOpened(new_tiles) => {
-                self.loading_state = LoadingState::Inactive;
+                if matches!(self.loading_state, LoadingState::LoadingTiles) {
+                    self.loading_state = LoadingState::Inactive;
+                }
 
-                if new_tiles.is_some() {
-                    let file = AsepriteFile::read_file(&new_tiles.as_ref().unwrap());
+                if let Some(new_tiles) = new_tiles {
+                    self.remember_directory(&new_tiles);
+                    let file = AsepriteFile::read_file(&new_tiles);
                     match file {
                         Ok(f) => {
-                            self.tiles_file = new_tiles;
-                            *self.tiles.borrow_mut() = Some(f);
+                            // tiles are the frames of the sheet
+                            let tile_size = f.width() as u16;
+                            let mut sheets = self.tiles.borrow_mut();
+                            let reloaded = sheets.iter().position(|sheet| sheet.file == new_tiles);
+
+                            // the size of the other sheets, the reloaded one may change it
+                            let others = sheets
+                                .iter()
+                                .enumerate()
+                                .find(|&(i, _)| Some(i) != reloaded)
+                                .map(|(_, sheet)| sheet.content.width() as u16);
+                            let error = sheet_error(&f, others).or_else(|| {
+                                (reloaded.is_none() && sheets.len() > u8::MAX as usize)
+                                    .then(|| "Too many tilesheets are loaded".to_string())
+                            });
+                            if let Some(error) = error {
+                                if self.loading_state.inactive() {
+                                    self.loading_state = LoadingState::Error;
+                                }
+                                return Command::perform(
+                                    Self::error_with_tiles(new_tiles, error),
+                                    Message::ErrorClosed,
+                                );
+                            }
 
-                            self.tile_selector.reset();
-                            self.map_viewer.refresh();
+                            let sheet = match reloaded {
+                                Some(sheet) => {
+                                    sheets[sheet].content = f;
+                                    sheet as u8
+                                }
+                                None => {
+                                    sheets.push(TileSheet {
+                                        name: new_tiles
+                                            .file_stem()
+                                            .map(|name| name.to_string_lossy().into_owned())
+                                            .unwrap_or_default(),
+                                        file: new_tiles,
+                                        content: f,
+                                    });
+                                    (sheets.len() - 1) as u8
+                                }
+                            };
+                            drop(sheets);
+
+                            self.tile_selector.tile_size = tile_size;
+                            self.map_viewer.tile_size = tile_size;
+                            if reloaded.is_some() && self.tile_selector.sheet() == sheet {
+                                // keep working with the same tiles after fixing the sheet
+                                self.tile_selector.reset(true);
+                            } else {
+                                self.tile_selector.select_sheet(sheet);
+                            }
+                            self.map_viewer.reload_tiles();
                         }
                         Err(err) => {
-                            self.loading_state = LoadingState::Error;
+                            if self.loading_state.inactive() {
+                                self.loading_state = LoadingState::Error;
+                            }
                             return Command::perform(
-                                Self::error_with_tiles(new_tiles.clone().unwrap(), err),
+                                Self::error_with_tiles(new_tiles, err.to_string()),
                                 Message::ErrorClosed,
                             );
                         }
@@ -326,60 +1359,761 @@ impl Application for TilemapEditor {
                 }
             }
 
+            Message::SheetSelected(sheet) => self.tile_selector.select_sheet(sheet),
             Message::TileSelected(i) => self.tile_selector.select(i),
+            // a map can use sheets that are not open, nothing is selected then
+            Message::SheetTileSelected(sheet, i) => {
+                if (sheet as usize) < self.tiles.borrow().len() {
+                    if sheet != self.tile_selector.sheet() {
+                        self.tile_selector.select_sheet(sheet);
+                    }
+                    self.tile_selector.select(i);
+                }
+            }
+            Message::TileToggled(i) => self.tile_selector.toggle(i),
+            Message::BlockSelected(from, to) => self.tile_selector.select_block(from, to),
             Message::TileUnSelected => self.tile_selector.unselect(),
+            Message::AnimationSelected(tag) => self.tile_selector.select_animation(tag),
+            Message::TileFilterChanged(filter) => self.tile_selector.set_filter(filter),
+            Message::ToggleTileIndices(show) => self.tile_selector.set_show_indices(show),
+            Message::SetSelectorColumns(columns) => self.tile_selector.set_tiles_per_line(columns),
 
             Message::ToolSelected(t) => self.map_viewer.tool = t,
             Message::HorizontalFlip(f) => self.horizontal_flip = f,
             Message::VerticalFlip(f) => self.vertical_flip = f,
-            Message::LayerChanged(layer) => {
-                self.map_viewer.layer = layer;
-                self.map_viewer.refresh()
+            Message::FillLayer => {
+                if let Some(tile) = self.brush() {
+                    self.map_viewer.fill_layer(Some(tile));
+                }
             }
+            Message::OffsetDialogOpened => {
+                self.offset_dialog.get_or_insert_with(Default::default);
+            }
+            Message::OffsetDialogClosed => self.offset_dialog = None,
+            Message::OffsetInput(input) => {
+                if let Some(dialog) = &mut self.offset_dialog {
+                    dialog.update(input);
+                }
+            }
+            Message::OffsetLayer(dx, dy, wrap) => {
+                self.offset_dialog = None;
+                self.map_viewer.offset_layer(dx, dy, wrap);
+            }
+            Message::ReplaceDialogOpened => {
+                self.replace_dialog = Some(ReplaceDialog::new(
+                    self.tile_selector.sheet(),
+                    self.tile_selector.get_selected(),
+                ));
+            }
+            Message::ReplaceDialogClosed => self.replace_dialog = None,
+            Message::ReplaceInput(input) => {
+                if let Some(dialog) = &mut self.replace_dialog {
+                    dialog.update(input);
+                }
+            }
+            Message::ReplaceTile(from, to, layer) => {
+                self.replace_dialog = None;
+                self.map_viewer.replace_tile(from, to, layer);
+            }
+            Message::StatisticsDialogOpened => {
+                self.statistics_dialog.get_or_insert_with(Default::default);
+            }
+            Message::StatisticsDialogClosed => self.statistics_dialog = None,
+            Message::StatisticsWholeMap(whole_map) => {
+                if let Some(dialog) = &mut self.statistics_dialog {
+                    dialog.set_whole_map(whole_map);
+                }
+            }
+            Message::MetadataDialogOpened => {
+                self.metadata_dialog =
+                    Some(MetadataDialog::new(self.map_viewer.metadata().clone()));
+            }
+            Message::MetadataDialogClosed => self.metadata_dialog = None,
+            Message::MetadataInput(input) => {
+                if let Some(dialog) = &mut self.metadata_dialog {
+                    dialog.update(input);
+                }
+            }
+            Message::MetadataChanged(metadata) => {
+                self.metadata_dialog = None;
+                self.map_viewer.set_metadata(metadata);
+            }
+            Message::PropertyNameChanged(name) => self.properties_panel.set_name(name),
+            Message::TilePropertyChanged(tile, name, property) => {
+                self.map_viewer.set_tile_property(tile, name, property)
+            }
+            Message::TilePropertyAdded(tile, property) => {
+                let name = self.properties_panel.name().trim().to_string();
+                self.properties_panel.set_name(String::new());
+                self.map_viewer
+                    .set_tile_property(tile, name, Some(property));
+            }
+            Message::RotateBrush => self.rotation = (self.rotation + 1) % 4,
+            Message::CycleTint => {
+                let next = TINTS
+                    .iter()
+                    .position(|tint| *tint == self.tint)
+                    .map_or(0, |i| (i + 1) % TINTS.len());
+                self.tint = TINTS[next];
+            }
+            Message::SetBrushSize(size) => self.map_viewer.set_brush_size(size),
+            Message::ChangeBrushSize(step) => {
+                let size = self.map_viewer.brush_size as i32 + step;
+                self.map_viewer
+                    .set_brush_size(size.clamp(1, u16::MAX as i32) as u16);
+            }
+            Message::SetLayerOpacity(layer, opacity) => self.map_viewer.set_opacity(layer, opacity),
+            Message::LayerChanged(layer) => {
+                self.map_viewer.layer = layer;
+                self.map_viewer.refresh()
+            }
+            Message::AddLayer => self.map_viewer.add_layer(),
+            Message::RemoveLayer(layer) => {
+                if self.loading_state.active() {
+                    return Command::none();
+                }
+                self.loading_state = LoadingState::ClearingMap;
+
+                return Command::perform(
+                    Self::confirm_remove_layer(
+                        layer,
+                        self.map_viewer.layer_name(layer).to_string(),
+                    ),
+                    Message::LayerRemoved,
+                );
+            }
+            Message::LayerRemoved(layer) => {
+                self.loading_state = LoadingState::Inactive;
 
-            Message::Redraw => self.map_viewer.refresh(),
-
-            Message::PaintTile(x, y) => self.map_viewer.set_tile(
-                x,
-                y,
-                self.tile_selector.get_selected().map_or_else(
-                    || self.map_viewer.get_tile(x, y, self.map_viewer.layer), // if no selected tile preserves current tile
-                    |tile| Some(Tile::new(tile, self.horizontal_flip, self.vertical_flip)), // otherwise overwrite it
-                ),
-            ),
-            Message::ClearTile(x, y) => self.map_viewer.set_tile(x, y, None),
-
-            Message::RectStarted => {
-                self.map_viewer.tile = self.tile_selector.get_selected().map_or(None, |tile| {
-                    Some(Tile::new(tile, self.horizontal_flip, self.vertical_flip))
-                })
+                if let Some(layer) = layer {
+                    self.map_viewer.remove_layer(layer);
+                }
+            }
+            Message::MoveLayer(up) => self.map_viewer.move_layer(up),
+            Message::CopyLayer(from, to) => self.map_viewer.copy_layer(from, to),
+            Message::SwapLayers(a, b) => self.map_viewer.swap_layers(a, b),
+
+            Message::ToggleGrid => self.map_viewer.toggle_grid(),
+            Message::ToggleCheckerboard => self.map_viewer.toggle_checkerboard(),
+            Message::SetChunkSize(chunk_size) => self.map_viewer.set_chunk_size(chunk_size),
+            Message::FitView => self.map_viewer.fit_view(),
+            Message::ZoomMap(factor, position) => self.map_viewer.zoom(factor, position),
+            Message::JumpTo(x, y) => self.map_viewer.centre_on(x, y),
+            Message::JumpInputChanged(input) => self.jump_input = input,
+            Message::TileInputChanged(input) => self.tile_input = input,
+            Message::TileNumberSubmitted(i) => self.tile_selector.select_and_reveal(i),
+            Message::ResetView => self.map_viewer.reset_view(),
+            Message::CycleEmptyColour => self.map_viewer.cycle_empty_colour(),
+
+            Message::Redraw | Message::AnimationTick => self.map_viewer.refresh(),
+            Message::CursorMovedTile(x, y) => self.hovered_tile = Some((x, y)),
+            Message::CursorLeftMap => self.hovered_tile = None,
+
+            // tiles painted before there is a tilesheet could not be drawn
+            Message::PaintTile(..) | Message::ClearTile(..) if self.tiles.borrow().is_empty() => {}
+            Message::PaintTile(x, y) => {
+                self.hovered_tile = Some((x, y));
+                if self.map_viewer.tool == Tool::Pen {
+                    if let Some(block) = self.block_brush() {
+                        for (dx, dy, tile) in block {
+                            if let (Some(x), Some(y)) = (x.checked_add(dx), y.checked_add(dy)) {
+                                self.map_viewer.paint(x, y, Some(tile));
+                            }
+                        }
+                        return Command::none();
+                    }
+                }
+
+                let (columns, rows) = self.map_viewer.brush_extent(x, y);
+                for x in columns {
+                    for y in rows.clone() {
+                        // each cell gets its own random tile
+                        let brush = if self.map_viewer.tool == Tool::Scatter {
+                            self.scatter_brush()
+                        } else {
+                            self.brush()
+                        };
+                        self.map_viewer.paint(
+                            x,
+                            y,
+                            brush.or_else(|| self.map_viewer.get_tile(x, y, self.map_viewer.layer)), // if no selected tile preserves current tile
+                        )
+                    }
+                }
+            }
+            Message::ClearTile(x, y) => {
+                self.hovered_tile = Some((x, y));
+                self.map_viewer.paint(x, y, None)
+            }
+            Message::PickTile(x, y) => {
+                if let Some(tile) = self.map_viewer.get_tile(x, y, self.map_viewer.layer) {
+                    if tile.sheet != self.tile_selector.sheet() {
+                        self.tile_selector.select_sheet(tile.sheet);
+                    }
+                    match tile.anim {
+                        Some(tag) => self.tile_selector.select_animation(tag),
+                        None => self.tile_selector.select(tile.value),
+                    }
+                    self.horizontal_flip = tile.h_flip;
+                    self.vertical_flip = tile.v_flip;
+                    self.rotation = tile.rotation;
+                    self.tint = tile.tint;
+                }
             }
 
+            Message::SelectRegion(x, y) => self.map_viewer.select_region(x, y),
+            Message::SelectMatching(x, y) => self.map_viewer.select_matching(x, y),
+            Message::SelectEnclosed(x, y) => self.map_viewer.select_enclosed(x, y),
+            Message::OpenReference => {
+                if self.loading_state.active() {
+                    return Command::none();
+                }
+                self.loading_state = LoadingState::OpeningReference;
+
+                return Command::perform(
+                    Self::open_reference(self.config.last_directory.clone()),
+                    Message::ReferenceOpened,
+                );
+            }
+            Message::ReferenceOpened(file) => {
+                self.loading_state = LoadingState::Inactive;
+
+                if let Some(file) = file {
+                    self.remember_directory(&file);
+                    match Reference::open(&file) {
+                        Ok(reference) => self.map_viewer.set_reference(Some(reference)),
+                        Err(err) => {
+                            self.loading_state = LoadingState::Error;
+                            return Command::perform(
+                                Self::error_with_reference(file, err.to_string()),
+                                Message::ErrorClosed,
+                            );
+                        }
+                    }
+                }
+            }
+            Message::ReferenceClosed => self.map_viewer.set_reference(None),
+            Message::CompareMap => {
+                if self.loading_state.active() {
+                    return Command::none();
+                }
+                self.loading_state = LoadingState::OpeningMap;
+
+                return Command::perform(
+                    Self::open_map(false, self.config.last_directory.clone()),
+                    Message::ComparedMapOpened,
+                );
+            }
+            Message::ComparedMapOpened(file) => {
+                self.loading_state = LoadingState::Inactive;
+
+                if let Some(file) = file {
+                    self.remember_directory(&file);
+                    match save::load_from_file(&file) {
+                        Ok(map) => {
+                            self.map_viewer.set_compared(Some(map));
+                            self.compared_file = Some(file);
+                        }
+                        Err(err) => {
+                            self.loading_state = LoadingState::Error;
+                            return Command::perform(
+                                Self::error_opening_map(file, err.to_string()),
+                                Message::ErrorClosed,
+                            );
+                        }
+                    }
+                }
+            }
+            Message::ComparisonClosed => {
+                self.map_viewer.set_compared(None);
+                self.compared_file = None;
+            }
+            Message::ReferenceShown(visible) => self
+                .map_viewer
+                .edit_reference(|reference| reference.visible = visible),
+            Message::ReferenceSnapped(snap) => self
+                .map_viewer
+                .edit_reference(|reference| reference.snap = snap),
+            Message::SetReferenceOpacity(opacity) => self
+                .map_viewer
+                .edit_reference(|reference| reference.opacity = opacity),
+            Message::SetReferenceScale(scale) => self
+                .map_viewer
+                .edit_reference(|reference| reference.scale = scale),
+            Message::MoveReference(dx, dy) => self.map_viewer.edit_reference(|reference| {
+                reference.offset = (reference.offset.0 + dx, reference.offset.1 + dy)
+            }),
+            Message::SelectRect(x, y, width, height) => {
+                self.map_viewer.select_rect(x, y, width, height)
+            }
+            Message::SelectAll => self.map_viewer.select_all(),
+            Message::InvertSelection => self.map_viewer.invert_selection(),
+            Message::MoveSelection(dx, dy) => self.map_viewer.move_selection(dx, dy),
+            Message::NudgeSelection(dx, dy, jump) => self.map_viewer.nudge_selection(dx, dy, jump),
+            Message::CopySelection => self.map_viewer.copy_selection(),
+            Message::StampAt(x, y) => self.map_viewer.stamp(x, y),
+            Message::MirrorClipboard(horizontal) => {
+                self.map_viewer.mirror_clipboard(horizontal);
+                self.map_viewer.tool = Tool::Stamp;
+            }
+            Message::DeleteSelection => self.map_viewer.fill_selection(None),
+            Message::FillSelection => {
+                if let Some(tile) = self.brush() {
+                    self.map_viewer.fill_selection(Some(tile));
+                }
+            }
+            Message::Deselect => self.map_viewer.deselect(),
+
+            Message::RectStarted => self.map_viewer.tile = self.brush(),
+
             Message::PaintRect(x, y, width, height) => {
                 self.map_viewer.fill_rect(x, y, width, height)
             }
+            Message::PaintRectOutline(x, y, width, height) => {
+                self.map_viewer.fill_rect_outline(x, y, width, height)
+            }
+            Message::PaintEllipse(x, y, width, height) => {
+                self.map_viewer.fill_ellipse(x, y, width, height)
+            }
         }
 
         Command::none()
     }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let mut subscriptions = vec![
+            time::every(AUTOSAVE_INTERVAL).map(|_| Message::AutosaveTick),
+            iced_native::subscription::events_with(window_event),
+        ];
+
+        if self.map_viewer.is_animated() {
+            subscriptions.push(time::every(ANIMATION_INTERVAL).map(|_| Message::AnimationTick));
+        }
+
+        // no shortcut while a dialog is open
+        if !self.loading_state.active() {
+            subscriptions.push(iced_native::subscription::events_with(shortcuts::shortcut));
+        }
+
+        Subscription::batch(subscriptions)
+    }
 }
 
 impl TilemapEditor {
+    /// Selected tile, with the flips and rotation of the toolbar
+    fn brush(&self) -> Option<Tile> {
+        self.tile_selector.get_selected_tile().map(|tile| Tile {
+            h_flip: self.horizontal_flip,
+            v_flip: self.vertical_flip,
+            rotation: self.rotation,
+            tint: self.tint,
+            ..tile
+        })
+    }
+
+    /// Selected block of tiles with their offset from the cursor, the whole block being
+    /// flipped and rotated as set in the toolbar
+    fn block_brush(&self) -> Option<TileBlock> {
+        let (width, height, block) = self.tile_selector.get_selected_block()?;
+
+        let block = block
+            .into_iter()
+            .map(|(mut dx, mut dy, tile)| {
+                if self.horizontal_flip {
+                    dx = width - 1 - dx;
+                }
+                if self.vertical_flip {
+                    dy = height - 1 - dy;
+                }
+                // each quarter turn swaps the sides of the block
+                for turn in 0..self.rotation % 4 {
+                    let side = if turn % 2 == 0 { height } else { width };
+                    (dx, dy) = (side - 1 - dy, dx);
+                }
+
+                let tile = Tile {
+                    h_flip: self.horizontal_flip,
+                    v_flip: self.vertical_flip,
+                    rotation: self.rotation,
+                    tint: self.tint,
+                    ..tile
+                };
+                (dx, dy, tile)
+            })
+            .collect();
+
+        Some(block)
+    }
+
+    /// Brush with a tile picked at random in the brush set
+    fn scatter_brush(&self) -> Option<Tile> {
+        let value = *self
+            .tile_selector
+            .brush_set()
+            .choose(&mut rand::thread_rng())?;
+
+        self.brush().map(|tile| Tile {
+            value,
+            anim: None,
+            ..tile
+        })
+    }
+
+    /// Button sending `message`, disabled without one or while a file operation is going on
+    fn menu_button<'a>(&self, label: &'a str, message: Option<Message>) -> Button<'a, Message> {
+        let button = Button::new(Text::new(label));
+        match message {
+            Some(message) if self.loading_state.inactive() => button.on_press(message),
+            _ => button,
+        }
+    }
+
+    /// Layers from the top, with their opacity, and buttons editing them
+    fn layer_panel(&self) -> Element<'_, Message> {
+        let layers = self.map_viewer.layers();
+        let single = layers.len() <= 1;
+        let active = self.map_viewer.layer;
+
+        // the active layer is copied onto, or swapped with, the one picked
+        let others: Vec<_> = layers
+            .iter()
+            .filter(|entry| entry.layer != active)
+            .cloned()
+            .collect();
+        let copy = pick_list(others.clone(), None, move |entry: LayerEntry| {
+            Message::CopyLayer(active, entry.layer)
+        })
+        .placeholder("Copy onto");
+        let swap = pick_list(others, None, move |entry: LayerEntry| {
+            Message::SwapLayers(active, entry.layer)
+        })
+        .placeholder("Swap with");
+
+        let list = layers
+            .into_iter()
+            .rev()
+            .fold(Column::new().spacing(2), |column, entry| {
+                let layer = entry.layer;
+                column.push(
+                    Row::new()
+                        .spacing(5)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Button::new(Text::new(entry.name))
+                                .width(Length::Fill)
+                                .style(SelectorTheme::pick(self.map_viewer.layer, layer))
+                                .on_press(Message::LayerChanged(layer)),
+                        )
+                        .push(
+                            slider(0.0..=1.0, self.map_viewer.opacity(layer), move |opacity| {
+                                Message::SetLayerOpacity(layer, opacity)
+                            })
+                            .step(0.05)
+                            .width(Length::Units(60)),
+                        ),
+                )
+            });
+
+        let remove = self.menu_button(
+            "Remove",
+            (!single).then_some(Message::RemoveLayer(self.map_viewer.layer)),
+        );
+
+        Column::new()
+            .width(Length::Units(200))
+            .spacing(5)
+            .push(Text::new("Layers"))
+            .push(scrollable(list).height(Length::Fill))
+            .push(
+                Row::new()
+                    .spacing(2)
+                    .push(self.menu_button("Add", Some(Message::AddLayer)))
+                    .push(remove)
+                    .push(self.menu_button("Up", Some(Message::MoveLayer(true))))
+                    .push(self.menu_button("Down", Some(Message::MoveLayer(false)))),
+            )
+            .push(Row::new().spacing(2).push(copy).push(swap))
+            .push(checkbox(
+                "Paint through all layers",
+                self.map_viewer.all_layers,
+                Message::AllLayersChanged,
+            ))
+            .push(Text::new("Overview"))
+            .push(self.map_viewer.minimap())
+            .push(Text::new("Tile properties"))
+            .push(
+                self.properties_panel.view(
+                    self.tile_selector.get_selected(),
+                    self.tile_selector
+                        .get_selected()
+                        .and_then(|tile| self.map_viewer.tile_properties(tile)),
+                ),
+            )
+            .into()
+    }
+
+    /// Name of the compared map and the number of cells differing from it, if there is one
+    fn comparison_view(&self) -> Element<'_, Message> {
+        let file = match (&self.compared_file, self.map_viewer.compared()) {
+            (Some(file), Some(_)) => file,
+            _ => return Space::new(Length::Shrink, Length::Shrink).into(),
+        };
+
+        let name = file.file_name().map_or_else(
+            || file.display().to_string(),
+            |name| name.to_string_lossy().into(),
+        );
+        let status = match self.map_viewer.differing_cells() {
+            0 => format!("Same tiles as {}", name),
+            1 => format!("1 cell differs from {}", name),
+            cells => format!("{} cells differ from {}", cells, name),
+        };
+
+        Row::new()
+            .spacing(5)
+            .align_items(Alignment::Center)
+            .push(Text::new(status))
+            .push(Button::new(Text::new("Stop comparing")).on_press(Message::ComparisonClosed))
+            .into()
+    }
+
+    /// Input of the number of a tile, pressing enter selecting it
+    fn tile_input(&self) -> Element<'_, Message> {
+        let count = self.tile_selector.tile_count();
+        let tile = self
+            .tile_input
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|&i| i < count);
+
+        let input = text_input(
+            "Select tile number",
+            &self.tile_input,
+            Message::TileInputChanged,
+        );
+        let input = match tile {
+            Some(i) => input.on_submit(Message::TileNumberSubmitted(i)),
+            None => input,
+        };
+
+        let column = Column::new().push(input);
+        if tile.is_none() && !self.tile_input.trim().is_empty() {
+            column
+                .push(Text::new(match count {
+                    0 => "There are no tiles yet".to_string(),
+                    _ => format!("Tiles go from 0 to {}", count - 1),
+                }))
+                .into()
+        } else {
+            column.into()
+        }
+    }
+
+    /// Input of the tile to centre the view on, pressing enter jumping to it
+    fn jump_input(&self) -> Element<'_, Message> {
+        let (width, height) = self.map_viewer.dimensions();
+        let target = self.jump_input.split_once(',').and_then(|(x, y)| {
+            let (x, y) = (x.trim().parse::<u16>().ok()?, y.trim().parse::<u16>().ok()?);
+            (x < width && y < height).then_some((x, y))
+        });
+
+        let input = text_input("Go to x, y", &self.jump_input, Message::JumpInputChanged)
+            .width(Length::Units(100));
+        let input = match target {
+            Some((x, y)) => input.on_submit(Message::JumpTo(x, y)),
+            None => input,
+        };
+
+        let row = Row::new().spacing(5).push(input);
+        if target.is_none() && !self.jump_input.trim().is_empty() {
+            row.push(Text::new(format!(
+                "Not a tile of the {}x{} map",
+                width, height
+            )))
+            .into()
+        } else {
+            row.into()
+        }
+    }
+
+    /// One tab per tilesheet, to pick the one shown in the selector
+    /// Every tilesheet loaded, for pick lists
+    fn sheet_entries(&self) -> Vec<SheetEntry> {
+        self.tiles
+            .borrow()
+            .iter()
+            .enumerate()
+            .map(|(i, sheet)| SheetEntry {
+                sheet: i as u8,
+                name: sheet.name.clone(),
+            })
+            .collect()
+    }
+
+    fn sheet_tabs(&self) -> Element<'_, Message> {
+        let sheets = self.tiles.borrow();
+        if sheets.is_empty() {
+            return Text::new("No file selected").into();
+        }
+
+        sheets
+            .iter()
+            .enumerate()
+            .fold(Row::new().spacing(2), |row, (i, sheet)| {
+                let i = i as u8;
+                row.push(
+                    Button::new(Text::new(sheet.name.clone()))
+                        .style(SelectorTheme::pick(self.tile_selector.sheet(), i))
+                        .on_press(Message::SheetSelected(i)),
+                )
+            })
+            .into()
+    }
+
+    /// Deletes the recovery file of the current map, its changes being saved or discarded
+    fn discard_recovery(&self) {
+        if let Some(recovery) = Recovery::for_map(self.map_file.as_deref()) {
+            recovery.remove();
+        }
+    }
+
+    /// Adds `file` to the recent files
+    fn remember_file(&mut self, file: PathBuf) {
+        self.remember_directory(&file);
+        self.recent_files.add(file);
+        self.save_recent_files();
+    }
+
+    /// Starts the next file dialogs in the directory of `file`
+    fn remember_directory(&mut self, file: &Path) {
+        let directory = file.parent().map(Path::to_path_buf);
+        if directory.is_some() && directory != self.config.last_directory {
+            self.config.last_directory = directory;
+            self.save_config();
+        }
+    }
+
+    fn save_config(&self) {
+        if let Err(err) = self.config.save() {
+            eprintln!("Could not save the configuration: {}", err);
+        }
+    }
+
+    /// Closes the window, keeping its size for the next launch
+    fn exit(&mut self) {
+        self.save_config();
+        self.exiting = true;
+    }
+
+    fn save_recent_files(&self) {
+        // not worth bothering the user with a dialog
+        if let Err(err) = self.recent_files.save() {
+            eprintln!("Could not save the recent files: {}", err);
+        }
+    }
+
+    /// Name and version of the editor, to give when reporting an issue
+    async fn show_about() {
+        AsyncMessageDialog::new()
+            .set_level(rfd::MessageLevel::Info)
+            .set_buttons(rfd::MessageButtons::Ok)
+            .set_title("About")
+            .set_description(&format!(
+                "{} {}\n{}",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION"),
+                env!("CARGO_PKG_DESCRIPTION")
+            ))
+            .show()
+            .await;
+    }
+
     async fn new_map(modified: bool) -> bool {
         // only case where we do not create a new map is modified and keep, corresponding to a NAND
         !(modified && keep_modifications().await)
     }
 
-    async fn open_map(modified: bool) -> Option<PathBuf> {
+    /// Returns `layers` if the user confirms clearing them, or nothing
+    async fn confirm_clear(layers: Vec<Layer>, description: String) -> Vec<Layer> {
+        let confirmed = AsyncMessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_buttons(rfd::MessageButtons::OkCancel)
+            .set_title("Clear tiles")
+            .set_description(&description)
+            .show()
+            .await;
+
+        if confirmed {
+            layers
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Returns `layer` if the user confirms removing it, or nothing
+    async fn confirm_remove_layer(layer: Layer, name: String) -> Option<Layer> {
+        AsyncMessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_buttons(rfd::MessageButtons::OkCancel)
+            .set_title("Remove layer")
+            .set_description(&format!(
+                "The {} layer and all its tiles will be removed.",
+                name
+            ))
+            .show()
+            .await
+            .then_some(layer)
+    }
+
+    async fn open_map(modified: bool, directory: Option<PathBuf>) -> Option<PathBuf> {
         if modified && keep_modifications().await {
             return None;
         }
 
-        return AsyncFileDialog::new()
+        file_dialog(directory)
             .add_filter("RON", &["ron", "RON"])
+            .add_filter("JSON", &["json"])
+            .add_filter("Binary", &["tmb"])
+            .add_filter("Tiled", &["tmx"])
             .pick_file()
             .await
-            .map(|h| h.path().into());
+            .map(|h| h.path().into())
+    }
+
+    /// Asks the user whether to restore the recovered changes.
+    ///
+    /// Returns the recovery to restore, the recovery file is deleted otherwise.
+    async fn offer_recovery(recovery: Recovery) -> Option<Recovery> {
+        let map = match &recovery.map_file {
+            Some(file) => format!("the map {:?}", file),
+            None => "a map never saved".to_string(),
+        };
+
+        if AsyncMessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .set_title("Unsaved changes found")
+            .set_description(&format!(
+                "Unsaved changes to {} were recovered. Do you want to restore them ?",
+                map
+            ))
+            .show()
+            .await
+        {
+            Some(recovery)
+        } else {
+            recovery.remove();
+            None
+        }
+    }
+
+    async fn open_recent(modified: bool, file: PathBuf) -> Option<PathBuf> {
+        if modified && keep_modifications().await {
+            return None;
+        }
+
+        Some(file)
     }
 
     async fn error_opening_map(file: PathBuf, err: String) {
@@ -395,19 +2129,70 @@ impl TilemapEditor {
             .await;
     }
 
-    async fn save_map(map: TileMap) -> Option<String> {
-        if let Some(file) = AsyncFileDialog::new()
-            .add_filter("RON", &["ron", "RON"])
-            .save_file()
-            .await
-            .map(|h| h.path().into())
-        {
-            return match save::save_in_file(map, file) {
-                Ok(_) => None,
-                Err(err) => Some(err.to_string()),
-            };
+    /// Saves the map in `file`, or prompts for one if there is none.
+    ///
+    /// Picking an existing file other than `current`, the file the map comes from, must be
+    /// confirmed. Returns the file where the map was saved, or `None` if the dialog was
+    /// cancelled.
+    async fn save_map(
+        map: TileMap,
+        file: Option<PathBuf>,
+        current: Option<PathBuf>,
+        directory: Option<PathBuf>,
+    ) -> Result<Option<PathBuf>, String> {
+        let file = match file {
+            Some(file) => file,
+            None => match file_dialog(directory)
+                .add_filter("RON", &["ron", "RON"])
+                .add_filter("JSON", &["json"])
+                .add_filter("Binary", &["tmb"])
+                .save_file()
+                .await
+            {
+                Some(handle) => handle.path().into(),
+                None => return Ok(None),
+            },
+        };
+
+        if file.exists() && current.as_ref() != Some(&file) && !confirm_overwrite(&file).await {
+            return Ok(None);
+        }
+
+        match save::save_in_file(map, file.clone()) {
+            Ok(_) => Ok(Some(file)),
+            Err(err) => Err(err.to_string()),
         }
+    }
+
+    /// Writes the exported map in a file picked by the user, then shows `warning` about what the
+    /// format lost
+    async fn export_map(
+        content: String,
+        format: ExportFormat,
+        warning: Option<String>,
+        directory: Option<PathBuf>,
+    ) -> Option<String> {
+        let (name, extensions) = format.filter();
+
+        let file: PathBuf = file_dialog(directory)
+            .add_filter(name, extensions)
+            .save_file()
+            .await?
+            .path()
+            .into();
 
+        if let Err(err) = fs::write(file, content) {
+            return Some(err.to_string());
+        }
+        if let Some(warning) = warning {
+            AsyncMessageDialog::new()
+                .set_level(rfd::MessageLevel::Warning)
+                .set_buttons(rfd::MessageButtons::Ok)
+                .set_title("Map exported")
+                .set_description(&warning)
+                .show()
+                .await;
+        }
         None
     }
 
@@ -421,22 +2206,36 @@ impl TilemapEditor {
             .await;
     }
 
-    async fn open_tiles(has_a_file: bool) -> Option<PathBuf> {
-        if has_a_file {
-            match AsyncMessageDialog::new().set_level(rfd::MessageLevel::Warning).set_buttons(rfd::MessageButtons::OkCancel).set_title("Tilesheet already loaded").set_description("A tilesheet is already loaded. Loading a new one will overwrite the previous one, and may break your map").show().await {
-                false => return None,
-                true => {},
-            }
-        }
-
-        return AsyncFileDialog::new()
+    async fn open_tiles(directory: Option<PathBuf>) -> Option<PathBuf> {
+        file_dialog(directory)
             .add_filter("aseprite", &["ase", "aseprite"])
             .pick_file()
             .await
-            .map(|h| h.path().into());
+            .map(|h| h.path().into())
     }
 
-    async fn error_with_tiles(file: PathBuf, err: AsepriteParseError) {
+    async fn open_reference(directory: Option<PathBuf>) -> Option<PathBuf> {
+        file_dialog(directory)
+            .add_filter("PNG", &["png", "PNG"])
+            .pick_file()
+            .await
+            .map(|h| h.path().into())
+    }
+
+    async fn error_with_reference(file: PathBuf, err: String) {
+        AsyncMessageDialog::new()
+            .set_level(rfd::MessageLevel::Error)
+            .set_buttons(rfd::MessageButtons::Ok)
+            .set_title("Error opening reference image")
+            .set_description(&format!(
+                "There was an error opening the image {:?}:\n{}",
+                file, err
+            ))
+            .show()
+            .await;
+    }
+
+    async fn error_with_tiles(file: PathBuf, err: String) {
         AsyncMessageDialog::new()
             .set_level(rfd::MessageLevel::Error)
             .set_buttons(rfd::MessageButtons::Ok)
@@ -454,10 +2253,56 @@ impl TilemapEditor {
 ///
 /// Returns `true` if they should be kept, or `false` if they should be overwritten.
 async fn keep_modifications() -> bool {
-    if !AsyncMessageDialog::new().set_level(rfd::MessageLevel::Warning).set_buttons(rfd::MessageButtons::YesNo).set_title("Map modified").set_description("The current tilemap has been modified since last save. Do you still want to open a new one ? All changes will be lost").show().await {
-        return true;
+    !AsyncMessageDialog::new().set_level(rfd::MessageLevel::Warning).set_buttons(rfd::MessageButtons::YesNo).set_title("Map modified").set_description("The current tilemap has been modified since last save. Do you still want to open a new one ? All changes will be lost").show().await
+}
+
+fn window_event(event: iced_native::Event, _status: iced_native::event::Status) -> Option<Message> {
+    match event {
+        iced_native::Event::Window(iced_native::window::Event::FileDropped(file)) => {
+            Some(Message::FileDropped(file))
+        }
+        iced_native::Event::Window(iced_native::window::Event::CloseRequested) => {
+            Some(Message::CloseRequested)
+        }
+        iced_native::Event::Window(iced_native::window::Event::Resized { width, height }) => {
+            Some(Message::WindowResized(width, height))
+        }
+        _ => None,
+    }
+}
+
+/// File dialog starting in `directory`, if there is one
+fn file_dialog(directory: Option<PathBuf>) -> AsyncFileDialog {
+    let dialog = AsyncFileDialog::new();
+    match directory {
+        Some(directory) => dialog.set_directory(directory),
+        None => dialog,
     }
-    return false;
+}
+
+/// Asks the user whether to close the window, losing the modifications of the map
+async fn confirm_close() -> bool {
+    AsyncMessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .set_title("Map modified")
+        .set_description("The current tilemap has been modified since last save. Do you still want to quit ? All changes will be lost")
+        .show()
+        .await
+}
+
+/// Asks the user whether `file`, which exists, may be replaced
+async fn confirm_overwrite(file: &Path) -> bool {
+    AsyncMessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_buttons(rfd::MessageButtons::OkCancel)
+        .set_title("File already exists")
+        .set_description(&format!(
+            "The file {:?} already exists. Do you want to replace it ?",
+            file
+        ))
+        .show()
+        .await
 }
 
 fn load_svg(name: &str) -> Svg {
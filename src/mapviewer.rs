@@ -1,34 +1,133 @@
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
 use asefile::AsepriteFile;
 use iced::{
-    canvas::{event::Status, Event, Frame},
-    mouse,
+    alignment,
+    canvas::{event::Status, Cursor, Event, Frame, Path, Stroke, Text},
+    keyboard, mouse,
     pure::{
         widget::{canvas, Canvas},
         Element,
     },
-    Color, Length, Point, Size,
+    Color, Length, Point, Rectangle, Size, Vector,
 };
 
 use crate::{
-    tilemap::{Layer, Tile, TileMap},
-    Message, Tiles,
+    minimap::MiniMap,
+    reference::{Reference, MAX_SCALE, MIN_SCALE},
+    tileimage::TileImages,
+    tilemap::{
+        Layer, LayerEntry, MapMetadata, MapTransform, Properties, PropertyValue, Tile, TileMap,
+    },
+    Message, TileSheet, Tiles,
 };
 
 pub struct MapViewer {
     pub modified: bool,
     pub tool: Tool,
+    /// Side of the square painted by the pen, centred on the cursor
+    pub brush_size: u16,
+    /// Axes painting and erasing are mirrored across
+    pub symmetry: Symmetry,
     pub tile: Option<Tile>,
     pub layer: Layer,
+    /// Whether painting and erasing go through every layer, see [`MapViewer::set_tile`]
+    pub all_layers: bool,
+    pub tile_size: u16,
+    pub show_grid: bool,
+    /// Number of cells between two guides, drawn heavier than the grid
+    pub chunk_size: Option<u16>,
+    pub grid_colour: Color,
+    pub empty_colour: Color,
+    /// Whether empty cells show a checkerboard instead of `empty_colour`
+    pub checkerboard: bool,
     map: TileMap,
+    /// Opacity of each layer of the map
+    opacity: Vec<f32>,
+    /// Cells selected by the magic wand or the selection tool
+    selection: HashSet<(u16, u16)>,
+    clipboard: Option<Clipboard>,
+    /// Scale of the map, relative to its default size
+    zoom: f32,
+    /// Position of the top left corner of the map in the canvas
+    translation: Vector,
+    /// Size of the canvas when it was last drawn
+    bounds: Cell<Size>,
+    minimap: MiniMap,
+    /// Image traced over, drawn under the tiles
+    reference: Option<Reference>,
+    /// Map the cells differing from are highlighted
+    compared: Option<TileMap>,
     cache: canvas::Cache,
     tiles: Tiles,
+    images: TileImages,
+    /// Origin of the animations
+    started: Instant,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Tool {
     Pen,
     Rect,
+    /// Rectangle of which only the border is painted
+    RectOutline,
+    Ellipse,
     Selection,
+    Picker,
+    MagicWand,
+    Stamp,
+    /// Pen painting a random tile of the brush set
+    Scatter,
+    /// Clears the tiles dragged over, like the right button does with the other tools
+    Eraser,
+}
+
+/// Mirror axes through the middle of the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    /// Left and right halves mirror each other
+    Vertical,
+    /// Top and bottom halves mirror each other
+    Horizontal,
+    Both,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 4] = [
+        Symmetry::None,
+        Symmetry::Vertical,
+        Symmetry::Horizontal,
+        Symmetry::Both,
+    ];
+}
+
+impl Display for Symmetry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Symmetry::None => "No symmetry",
+                Symmetry::Vertical => "Vertical axis",
+                Symmetry::Horizontal => "Horizontal axis",
+                Symmetry::Both => "Both axes",
+            }
+        )
+    }
+}
+
+/// Tiles copied from the active layer, cells outside of the selection being `None`
+struct Clipboard {
+    width: u16,
+    height: u16,
+    tiles: Vec<Vec<Option<Tile>>>,
 }
 
 impl MapViewer {
@@ -37,34 +136,477 @@ impl MapViewer {
             modified: false,
             map: Default::default(),
             tile: None,
-            layer: Layer::Background,
+            symmetry: Symmetry::None,
+            layer: 0,
+            all_layers: false,
+            tile_size: 8,
+            show_grid: true,
+            chunk_size: None,
+            grid_colour: Color::new(0.7, 0.7, 0.7, 1.0),
+            empty_colour: EMPTY_COLOURS[0],
+            checkerboard: false,
+            opacity: vec![1.0; 2],
+            selection: HashSet::new(),
+            clipboard: None,
+            zoom: 1.0,
+            translation: Vector::new(0.0, 0.0),
+            bounds: Cell::new(Size::ZERO),
+            minimap: Default::default(),
+            reference: None,
+            compared: None,
             cache: Default::default(),
             tiles,
+            images: Default::default(),
+            started: Instant::now(),
             tool: Tool::Pen,
+            brush_size: 1,
         }
     }
 
     pub fn view(&self) -> Element<'_, Message> {
-        let (width, height) = self.map.get_dimensions();
-        let tile_side = (8.0 * SCALE_FACTOR + BORDER_SIZE) as u16;
-
+        // fill the available space, only the visible part of the map gets drawn
         Canvas::new(self)
-            .width(Length::Units(width * tile_side - 1))
-            .height(Length::Units(height * tile_side - 1))
+            .width(Length::Fill)
+            .height(Length::Fill)
             .into()
     }
 
+    /// Overview of the map, outlining the part of it which is visible
+    pub fn minimap(&self) -> Element<'_, Message> {
+        self.minimap
+            .view(&self.map, self.visible_tiles(self.bounds.get()))
+    }
+
+    /// Sets the cell `(x, y)` of the active layer to `value`.
+    ///
+    /// With `all_layers`, the cell is also emptied in every other layer: erasing clears it
+    /// through the whole map, and a tile painted is left alone on it, in the active layer.
     pub fn set_tile(&mut self, x: u16, y: u16, value: Option<Tile>) {
+        let layers = if self.all_layers {
+            0..self.map.layer_count()
+        } else {
+            self.layer..self.layer + 1
+        };
+        let value_in = |layer: Layer| if layer == self.layer { value } else { None };
+
+        // erasing an empty cell, or painting a tile over itself, changes nothing
+        if layers
+            .clone()
+            .all(|layer| self.map.get_layer_tile(x, y, layer) == value_in(layer))
+        {
+            return;
+        }
+
         self.modified = true;
-        self.map.set_tile(x, y, value, self.layer);
+        for layer in layers {
+            self.map.set_tile(x, y, value_in(layer), layer);
+        }
         self.cache.clear();
+        self.minimap.clear();
+    }
+
+    pub fn set_brush_size(&mut self, brush_size: u16) {
+        self.brush_size = brush_size.clamp(1, MAX_BRUSH_SIZE);
+    }
+
+    /// Columns and rows of the brush square centred on `(x, y)`, clipped to the map
+    pub fn brush_extent(&self, x: u16, y: u16) -> (Range<u16>, Range<u16>) {
+        let (width, height) = self.map.get_dimensions();
+        let size = match self.tool {
+            Tool::Pen | Tool::Scatter => self.brush_size,
+            _ => 1,
+        };
+        let extent = |centre: u16, side: u16| {
+            let start = centre.saturating_sub((size - 1) / 2);
+            start..start.saturating_add(size).min(side)
+        };
+
+        (extent(x, width), extent(y, height))
+    }
+
+    /// Sets the tile at `(x, y)` and at its images across the symmetry axes, the mirrored
+    /// tiles being flipped to match
+    pub fn paint(&mut self, x: u16, y: u16, value: Option<Tile>) {
+        self.set_tile(x, y, value);
+
+        let (width, height) = self.map.get_dimensions();
+        let mirror_x = matches!(self.symmetry, Symmetry::Vertical | Symmetry::Both);
+        let mirror_y = matches!(self.symmetry, Symmetry::Horizontal | Symmetry::Both);
+        // cells outside of the map have no image in it
+        let mirrored_x = (mirror_x && x < width).then(|| width - 1 - x);
+        let mirrored_y = (mirror_y && y < height).then(|| height - 1 - y);
+
+        if let Some(mirrored_x) = mirrored_x {
+            self.set_tile(mirrored_x, y, value.map(Tile::flipped_horizontally));
+        }
+        if let Some(mirrored_y) = mirrored_y {
+            self.set_tile(x, mirrored_y, value.map(Tile::flipped_vertically));
+        }
+        if let (Some(mirrored_x), Some(mirrored_y)) = (mirrored_x, mirrored_y) {
+            self.set_tile(
+                mirrored_x,
+                mirrored_y,
+                value.map(|tile| tile.flipped_horizontally().flipped_vertically()),
+            );
+        }
     }
 
     pub fn get_tile(&self, x: u16, y: u16, layer: Layer) -> Option<Tile> {
-        let tiles = self.map.get_tile(x, y);
-        match layer {
-            Layer::Background => tiles.0,
-            Layer::Foreground => tiles.1,
+        self.map.get_layer_tile(x, y, layer)
+    }
+
+    pub fn layers(&self) -> Vec<LayerEntry> {
+        self.map.layers()
+    }
+
+    pub fn layer_name(&self, layer: Layer) -> &str {
+        self.map.layer_name(layer)
+    }
+
+    pub fn opacity(&self, layer: Layer) -> f32 {
+        self.opacity.get(layer).copied().unwrap_or(1.0)
+    }
+
+    pub fn set_opacity(&mut self, layer: Layer, opacity: f32) {
+        if let Some(current) = self.opacity.get_mut(layer) {
+            *current = opacity;
+            self.cache.clear();
+        }
+    }
+
+    /// Adds an empty layer above the active one, and makes it active
+    pub fn add_layer(&mut self) {
+        let layer = self.layer + 1;
+        let name = format!("Layer {}", self.map.layer_count() + 1);
+
+        self.modified = true;
+        self.map.add_layer(layer, name);
+        self.opacity.insert(layer, 1.0);
+        self.layer = layer;
+        self.cache.clear();
+        self.minimap.clear();
+    }
+
+    /// Removes `layer`, unless it is the last one
+    pub fn remove_layer(&mut self, layer: Layer) {
+        let count = self.map.layer_count();
+        if count <= 1 || layer >= count {
+            return;
+        }
+
+        self.modified = true;
+        self.map.remove_layer(layer);
+        self.opacity.remove(layer);
+        if self.layer > layer || self.layer == count - 1 {
+            self.layer -= 1;
+        }
+        self.cache.clear();
+        self.minimap.clear();
+    }
+
+    /// Swaps the active layer with the one above or below it
+    pub fn move_layer(&mut self, up: bool) {
+        let other = if up {
+            self.layer + 1
+        } else {
+            match self.layer.checked_sub(1) {
+                Some(other) => other,
+                None => return,
+            }
+        };
+        self.swap_layers(self.layer, other);
+    }
+
+    /// Exchanges the layers `a` and `b`, the active one following its tiles.
+    ///
+    /// There is no undo history, swapping them again is the only way back.
+    pub fn swap_layers(&mut self, a: Layer, b: Layer) {
+        let count = self.map.layer_count();
+        if a == b || a >= count || b >= count {
+            return;
+        }
+
+        self.modified = true;
+        self.map.swap_layers(a, b);
+        self.opacity.swap(a, b);
+        if self.layer == a {
+            self.layer = b;
+        } else if self.layer == b {
+            self.layer = a;
+        }
+        self.cache.clear();
+        self.minimap.clear();
+    }
+
+    /// Selects the region of the active layer around `(x, y)` sharing its tile
+    pub fn select_region(&mut self, x: u16, y: u16) {
+        self.selection = self.map.region(x, y, self.layer);
+        self.cache.clear();
+    }
+
+    /// Selects the empty area around `(x, y)`, up to the tiles of any layer
+    pub fn select_enclosed(&mut self, x: u16, y: u16) {
+        self.selection = self.map.enclosed_region(x, y);
+        self.cache.clear();
+    }
+
+    /// Selects the cells of the active layer holding the tile of `(x, y)`, connected or not
+    pub fn select_matching(&mut self, x: u16, y: u16) {
+        self.selection = self.map.matching(x, y, self.layer);
+        self.cache.clear();
+    }
+
+    /// Selects the cells of the rectangle, clipped to the map
+    pub fn select_rect(&mut self, x: u16, y: u16, width: i32, height: i32) {
+        let (map_width, map_height) = self.map.get_dimensions();
+        let min_x = i32::min(x as i32, x as i32 + width).max(0);
+        let min_y = i32::min(y as i32, y as i32 + height).max(0);
+        let max_x = (min_x + width.abs()).min(map_width as i32);
+        let max_y = (min_y + height.abs()).min(map_height as i32);
+
+        self.selection = (min_x..max_x)
+            .flat_map(|x| (min_y..max_y).map(move |y| (x as u16, y as u16)))
+            .collect();
+        self.cache.clear();
+    }
+
+    pub fn select_all(&mut self) {
+        let (width, height) = self.map.get_dimensions();
+        self.selection = (0..width)
+            .flat_map(|x| (0..height).map(move |y| (x, y)))
+            .collect();
+        self.cache.clear();
+    }
+
+    /// Selects the cells which are not, and only them
+    pub fn invert_selection(&mut self) {
+        let (width, height) = self.map.get_dimensions();
+        self.selection = (0..width)
+            .flat_map(|x| (0..height).map(move |y| (x, y)))
+            .filter(|cell| !self.selection.contains(cell))
+            .collect();
+        self.cache.clear();
+    }
+
+    /// Copies the selected tiles of the active layer, to be stamped
+    pub fn copy_selection(&mut self) {
+        let (min_x, max_x, min_y, max_y) = match (
+            self.selection.iter().map(|&(x, _)| x).min(),
+            self.selection.iter().map(|&(x, _)| x).max(),
+            self.selection.iter().map(|&(_, y)| y).min(),
+            self.selection.iter().map(|&(_, y)| y).max(),
+        ) {
+            (Some(min_x), Some(max_x), Some(min_y), Some(max_y)) => (min_x, max_x, min_y, max_y),
+            // nothing selected
+            _ => return,
+        };
+
+        self.clipboard = Some(Clipboard {
+            width: max_x - min_x + 1,
+            height: max_y - min_y + 1,
+            tiles: (min_x..=max_x)
+                .map(|x| {
+                    (min_y..=max_y)
+                        .map(|y| {
+                            if self.selection.contains(&(x, y)) {
+                                self.map.get_layer_tile(x, y, self.layer)
+                            } else {
+                                None
+                            }
+                        })
+                        .collect()
+                })
+                .collect(),
+        });
+    }
+
+    /// Paints the copied tiles with their top left corner at `(x, y)`, clipped to the map
+    pub fn stamp(&mut self, x: i32, y: i32) {
+        let clipboard = match self.clipboard.take() {
+            Some(clipboard) => clipboard,
+            None => return,
+        };
+
+        for (dx, column) in clipboard.tiles.iter().enumerate() {
+            for (dy, tile) in column.iter().enumerate() {
+                let (x, y) = (x + dx as i32, y + dy as i32);
+                if let (Some(tile), Ok(x), Ok(y)) = (tile, u16::try_from(x), u16::try_from(y)) {
+                    self.set_tile(x, y, Some(*tile));
+                }
+            }
+        }
+
+        self.clipboard = Some(clipboard);
+    }
+
+    /// Mirrors the copied tiles left to right, or top to bottom if not `horizontal`, each tile
+    /// being flipped along with its place
+    pub fn mirror_clipboard(&mut self, horizontal: bool) {
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => clipboard,
+            None => return,
+        };
+
+        if horizontal {
+            clipboard.tiles.reverse();
+        } else {
+            clipboard
+                .tiles
+                .iter_mut()
+                .for_each(|column| column.reverse());
+        }
+        for tile in clipboard.tiles.iter_mut().flatten().flatten() {
+            *tile = if horizontal {
+                tile.flipped_horizontally()
+            } else {
+                tile.flipped_vertically()
+            };
+        }
+    }
+
+    /// Top left corner of the stamp under `(x, y)`, stamps being laid side by side from
+    /// `(start_x, start_y)` so that a stroke tiles the pattern
+    fn stamp_origin(&self, start_x: u16, start_y: u16, x: u16, y: u16) -> Option<(i32, i32)> {
+        let clipboard = self.clipboard.as_ref()?;
+        let align = |start: u16, position: u16, side: u16| {
+            let (start, side) = (start as i32, side as i32);
+            start + (position as i32 - start).div_euclid(side) * side
+        };
+
+        Some((
+            align(start_x, x, clipboard.width),
+            align(start_y, y, clipboard.height),
+        ))
+    }
+
+    /// Moves the selected tiles of the active layer by `(dx, dy)` along with the selection,
+    /// the tiles moved out of the map being lost
+    pub fn move_selection(&mut self, dx: i32, dy: i32) {
+        let (width, height) = self.map.get_dimensions();
+        let moved: Vec<_> = self
+            .selection
+            .iter()
+            .map(|&(x, y)| ((x, y), self.map.get_layer_tile(x, y, self.layer)))
+            .collect();
+
+        for &((x, y), _) in &moved {
+            self.map.set_tile(x, y, None, self.layer);
+        }
+
+        self.selection.clear();
+        for ((x, y), tile) in moved {
+            let (x, y) = (x as i32 + dx, y as i32 + dy);
+            if (0..width as i32).contains(&x) && (0..height as i32).contains(&y) {
+                // like stamps, empty cells leave the tiles below them
+                if tile.is_some() {
+                    self.map.set_tile(x as u16, y as u16, tile, self.layer);
+                }
+                self.selection.insert((x as u16, y as u16));
+            }
+        }
+
+        self.modified = true;
+        self.cache.clear();
+        self.minimap.clear();
+    }
+
+    /// Moves the selection and its tiles by `(dx, dy)` steps, of a chunk with `jump` and of a
+    /// cell otherwise, only as far as it stays in the map
+    pub fn nudge_selection(&mut self, dx: i32, dy: i32, jump: bool) {
+        let step = match self.chunk_size {
+            Some(chunk_size) if jump => chunk_size as i32,
+            _ if jump => DEFAULT_JUMP,
+            _ => 1,
+        };
+        let (width, height) = self.map.get_dimensions();
+
+        let xs = self.selection.iter().map(|&(x, _)| x as i32);
+        let ys = self.selection.iter().map(|&(_, y)| y as i32);
+        let (min_x, max_x) = match (xs.clone().min(), xs.max()) {
+            (Some(min), Some(max)) => (min, max),
+            _ => return,
+        };
+        let (min_y, max_y) = (ys.clone().min().unwrap_or(0), ys.max().unwrap_or(0));
+
+        let dx = (dx * step).clamp(-min_x, width as i32 - 1 - max_x);
+        let dy = (dy * step).clamp(-min_y, height as i32 - 1 - max_y);
+        if (dx, dy) != (0, 0) {
+            self.move_selection(dx, dy);
+        }
+    }
+
+    pub fn deselect(&mut self) {
+        self.selection.clear();
+        self.cache.clear();
+    }
+
+    /// Sets every selected cell of the active layer to `tile`, `None` deleting them.
+    ///
+    /// The editor keeps no history of the changes, so this cannot be undone: it is only made a
+    /// single change of the map, marking it modified and redrawing it once.
+    pub fn fill_selection(&mut self, tile: Option<Tile>) {
+        if self.selection.is_empty() {
+            return;
+        }
+
+        self.modified = true;
+        for &(x, y) in &self.selection {
+            self.map.set_tile(x, y, tile, self.layer);
+        }
+        self.cache.clear();
+        self.minimap.clear();
+    }
+
+    pub fn copy_layer(&mut self, from: Layer, to: Layer) {
+        self.modified = true;
+        self.map.copy_layer(from, to);
+        self.cache.clear();
+        self.minimap.clear();
+    }
+
+    pub fn clear_layer(&mut self, layer: Layer) {
+        self.modified = true;
+        self.map.clear_layer(layer);
+        self.cache.clear();
+        self.minimap.clear();
+    }
+
+    /// Fills the active layer with `tile`
+    pub fn fill_layer(&mut self, tile: Option<Tile>) {
+        self.modified = true;
+        self.map.fill_layer(tile, self.layer);
+        self.cache.clear();
+        self.minimap.clear();
+    }
+
+    pub fn offset_layer(&mut self, dx: i32, dy: i32, wrap: bool) {
+        self.modified = true;
+        self.map.offset_layer(dx, dy, wrap, self.layer);
+        self.cache.clear();
+        self.minimap.clear();
+    }
+
+    pub fn replace_tile(&mut self, from: (u8, u32), to: (u8, u32), layer: Option<Layer>) {
+        self.modified = true;
+        self.map.replace_tile(from, to, layer);
+        self.cache.clear();
+        self.minimap.clear();
+    }
+
+    pub fn transform_map(&mut self, transform: MapTransform) {
+        self.modified = true;
+        self.selection.clear();
+        self.map.transform(transform);
+        self.cache.clear();
+        self.minimap.clear();
+    }
+
+    pub fn crop_to_content(&mut self) {
+        if self.map.crop_to_content() {
+            self.modified = true;
+            self.selection.clear();
+            self.cache.clear();
+            self.minimap.clear();
         }
     }
 
@@ -79,29 +621,407 @@ impl MapViewer {
         }
     }
 
+    pub fn fill_rect_outline(&mut self, x: u16, y: u16, width: i32, height: i32) {
+        for (x, y) in rect_outline_cells(x, y, width, height) {
+            self.set_tile(x as u16, y as u16, self.tile);
+        }
+    }
+
+    pub fn fill_ellipse(&mut self, x: u16, y: u16, width: i32, height: i32) {
+        for (x, y) in ellipse_cells(x, y, width, height) {
+            self.set_tile(x as u16, y as u16, self.tile);
+        }
+    }
+
     /// Clear the cache and force redrawing
     pub fn refresh(&mut self) {
         self.cache.clear();
     }
 
+    /// Whether the map has animated tiles, to be redrawn every [`ANIMATION_INTERVAL`]
+    pub fn is_animated(&self) -> bool {
+        self.map.is_animated()
+    }
+
+    /// Switch the colour drawn under empty cells to the next preset
+    pub fn cycle_empty_colour(&mut self) {
+        let current = EMPTY_COLOURS
+            .iter()
+            .position(|colour| *colour == self.empty_colour)
+            .unwrap_or(0);
+
+        self.empty_colour = EMPTY_COLOURS[(current + 1) % EMPTY_COLOURS.len()];
+        self.cache.clear();
+    }
+
+    pub fn reference(&self) -> Option<&Reference> {
+        self.reference.as_ref()
+    }
+
+    pub fn set_reference(&mut self, reference: Option<Reference>) {
+        self.reference = reference;
+        self.cache.clear();
+    }
+
+    pub fn compared(&self) -> Option<&TileMap> {
+        self.compared.as_ref()
+    }
+
+    /// Highlights the cells which differ from `map` on any layer, until set to `None`
+    pub fn set_compared(&mut self, map: Option<TileMap>) {
+        self.compared = map;
+        self.cache.clear();
+    }
+
+    /// Number of cells differing from the compared map on any layer
+    pub fn differing_cells(&self) -> usize {
+        self.compared.as_ref().map_or(0, |compared| {
+            self.map
+                .diff(compared)
+                .into_iter()
+                .map(|(x, y, _)| (x, y))
+                .collect::<HashSet<_>>()
+                .len()
+        })
+    }
+
+    /// Changes the reference image with `edit`, if there is one
+    pub fn edit_reference(&mut self, edit: impl FnOnce(&mut Reference)) {
+        if let Some(reference) = &mut self.reference {
+            edit(reference);
+            reference.opacity = reference.opacity.clamp(0.0, 1.0);
+            reference.scale = reference.scale.clamp(MIN_SCALE, MAX_SCALE);
+            if reference.snap {
+                reference.snap(self.tile_size);
+            }
+            self.cache.clear();
+        }
+    }
+
+    pub fn toggle_checkerboard(&mut self) {
+        self.checkerboard = !self.checkerboard;
+        self.cache.clear();
+    }
+
+    pub fn toggle_grid(&mut self) {
+        self.show_grid = !self.show_grid;
+        self.cache.clear();
+    }
+
+    /// Draws a guide every `chunk_size` cells, none below 2 as the grid already is one
+    pub fn set_chunk_size(&mut self, chunk_size: Option<u16>) {
+        self.chunk_size = chunk_size.filter(|&size| size >= 2);
+        self.cache.clear();
+    }
+
+    /// Forget decoded tiles after the tilesheet changed, and redraw
+    pub fn reload_tiles(&mut self) {
+        self.images.clear();
+        self.refresh();
+    }
+
+    pub fn dimensions(&self) -> (u16, u16) {
+        self.map.get_dimensions()
+    }
+
     pub fn get_map_instant(&self) -> TileMap {
         self.map.clone()
     }
 
+    pub fn metadata(&self) -> &MapMetadata {
+        self.map.metadata()
+    }
+
+    pub fn set_metadata(&mut self, metadata: MapMetadata) {
+        if *self.map.metadata() != metadata {
+            self.map.set_metadata(metadata);
+            self.modified = true;
+        }
+    }
+
+    pub fn tile_counts(&self, layer: Option<Layer>) -> HashMap<(u8, u32), u32> {
+        self.map.tile_counts(layer)
+    }
+
+    pub fn tile_properties(&self, value: u32) -> Option<&Properties> {
+        self.map.tile_properties().get(&value)
+    }
+
+    pub fn set_tile_property(&mut self, value: u32, name: String, property: Option<PropertyValue>) {
+        let current = self.tile_properties(value).and_then(|p| p.get(&name));
+        if current != property.as_ref() {
+            self.map.set_tile_property(value, name, property);
+            self.modified = true;
+        }
+    }
+
     pub fn set_entire_map(&mut self, map: TileMap) {
+        self.opacity = vec![1.0; map.layer_count()];
+        self.layer = self.layer.min(map.layer_count() - 1);
+        self.selection.clear();
         self.map = map;
         self.modified = false;
         self.cache.clear();
+        self.minimap.clear();
+    }
+
+    /// Scales the map so that it fits in the canvas, centred
+    pub fn fit_view(&mut self) {
+        let bounds = self.bounds.get();
+        let (width, height) = self.map.get_dimensions();
+        if width == 0 || height == 0 || bounds.width <= 0.0 || bounds.height <= 0.0 {
+            return;
+        }
+
+        let tile_side = f32::min(bounds.width / width as f32, bounds.height / height as f32);
+        self.zoom =
+            ((tile_side - BORDER_SIZE) / (self.tile_size as f32 * SCALE_FACTOR)).max(MIN_ZOOM);
+        self.centre(false);
+    }
+
+    /// Shows the map at its default size, centred
+    pub fn reset_view(&mut self) {
+        self.zoom = 1.0;
+        self.centre(true);
+    }
+
+    /// Multiplies the zoom by `factor`, the point of the map at `position` in the canvas
+    /// staying in place
+    pub fn zoom(&mut self, factor: f32, position: Point) {
+        let old_side = self.tile_side();
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let ratio = self.tile_side() / old_side;
+
+        self.translation = Vector::new(
+            position.x - (position.x - self.translation.x) * ratio,
+            position.y - (position.y - self.translation.y) * ratio,
+        );
+        self.cache.clear();
+    }
+
+    /// Moves the view so that the cell `(x, y)` is in its centre
+    pub fn centre_on(&mut self, x: u16, y: u16) {
+        let bounds = self.bounds.get();
+        let tile_side = self.tile_side();
+
+        self.translation = Vector::new(
+            bounds.width / 2.0 - (x as f32 + 0.5) * tile_side,
+            bounds.height / 2.0 - (y as f32 + 0.5) * tile_side,
+        );
+        self.cache.clear();
+    }
+
+    /// Centres the map in the canvas, or with `clamp` puts it against the top or left side
+    /// it overflows, as it could not be scrolled to otherwise
+    fn centre(&mut self, clamp: bool) {
+        let bounds = self.bounds.get();
+        let (width, height) = self.map.get_dimensions();
+        let tile_side = self.tile_side();
+
+        let offset = |space: f32, count: u16| {
+            let offset = (space - count as f32 * tile_side) / 2.0;
+            if clamp {
+                offset.max(0.0)
+            } else {
+                offset
+            }
+        };
+
+        self.translation = Vector::new(offset(bounds.width, width), offset(bounds.height, height));
+        self.cache.clear();
+    }
+
+    /// Translation of the map rounded to whole pixels, for the tiles drawn in the frames of
+    /// the map to keep their pixels on the screen ones
+    fn pixel_translation(&self) -> Vector {
+        Vector::new(self.translation.x.round(), self.translation.y.round())
+    }
+
+    /// Side of a tile on screen, including its border
+    fn tile_side(&self) -> f32 {
+        self.tile_size as f32 * SCALE_FACTOR * self.zoom + BORDER_SIZE
+    }
+
+    /// Map cell under the cursor, if any
+    fn hovered_tile(&self, bounds: Rectangle, cursor: Cursor) -> Option<(u16, u16)> {
+        let position = cursor.position_in(&bounds)? - self.pixel_translation();
+        if position.x < 0.0 || position.y < 0.0 {
+            return None;
+        }
+
+        let tile_side = self.tile_side();
+        let x = (position.x / tile_side).floor() as u16;
+        let y = (position.y / tile_side).floor() as u16;
+
+        // the canvas may be larger than the map
+        let (width, height) = self.map.get_dimensions();
+        (x < width && y < height).then_some((x, y))
+    }
+
+    /// Columns and rows of the map which are at least partially inside a canvas of size `bounds`
+    fn visible_tiles(&self, bounds: Size) -> (Range<u16>, Range<u16>) {
+        let (width, height) = self.map.get_dimensions();
+        let tile_side = self.tile_side();
+        let range = |offset: f32, side: f32, count: u16| {
+            let first = (-offset / tile_side).floor().max(0.0) as u16;
+            let last = ((side - offset) / tile_side).ceil().max(0.0) as u16;
+            u16::min(first, count)..u16::min(last, count)
+        };
+
+        (
+            range(self.translation.x, bounds.width, width),
+            range(self.translation.y, bounds.height, height),
+        )
+    }
+
+    fn draw_tile(
+        &self,
+        tile: Tile,
+        x: u16,
+        y: u16,
+        frame: &mut Frame,
+        sheets: &[TileSheet],
+        opacity: f32,
+    ) {
+        let tiles = match sheets.get(tile.sheet as usize) {
+            Some(sheet) => &sheet.content,
+            // the sheet of the tile is not loaded
+            None => return,
+        };
+
+        let tile = match tile.anim {
+            Some(tag) if tag < tiles.num_tags() => Tile {
+                value: self.animation_frame(tiles, tag),
+                anim: None,
+                ..tile
+            },
+            _ => tile,
+        };
+
+        if tile.value < tiles.num_frames() {
+            // this is a valid index for the current tiles
+            let image = self.images.get(tiles, tile, self.tile_size);
+            image.draw(
+                frame,
+                Point::new(x as f32 * self.tile_side(), y as f32 * self.tile_side()),
+                SCALE_FACTOR * self.zoom,
+                opacity,
+            );
+        }
+    }
+
+    /// Draws `tile` as it would be painted at `(x, y)`, hiding the other layers when painting
+    /// through all of them
+    fn draw_preview(&self, tile: Tile, x: u16, y: u16, frame: &mut Frame, sheets: &[TileSheet]) {
+        if self.all_layers {
+            let side = self.tile_side();
+            frame.fill_rectangle(
+                Point::new(x as f32 * side, y as f32 * side),
+                Size::new(side, side),
+                self.empty_colour,
+            );
+        }
+        self.draw_tile(tile, x, y, frame, sheets, 1.0);
+    }
+
+    /// Frame of the animation `tag` to show now, the tag being always played forward
+    fn animation_frame(&self, tiles: &AsepriteFile, tag: u32) -> u32 {
+        let tag = tiles.tag(tag);
+        let frames = tag.from_frame()..=tag.to_frame();
+
+        let total: u64 = frames
+            .clone()
+            .map(|i| tiles.frame(i).duration() as u64)
+            .sum();
+        if total == 0 {
+            return tag.from_frame();
+        }
+
+        let mut time = self.started.elapsed().as_millis() as u64 % total;
+        for i in frames {
+            let duration = tiles.frame(i).duration() as u64;
+            if time < duration {
+                return i;
+            }
+            time -= duration;
+        }
+        tag.to_frame()
     }
 }
 
 const SCALE_FACTOR: f32 = 2.0;
+/// Smallest zoom fitting a map can lead to
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 16.0;
+/// Zoom factor of one line of the mouse wheel
+const ZOOM_STEP: f32 = 1.1;
+/// Scrolling of a touchpad making up one line of the mouse wheel
+const PIXELS_PER_LINE: f32 = 50.0;
+
+const MAX_BRUSH_SIZE: u16 = 16;
+
+/// Cells the selection is nudged by with shift, when there are no chunks
+const DEFAULT_JUMP: i32 = 8;
+
+pub const ANIMATION_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Presets for the colour of empty cells, the first one being the default
+const EMPTY_COLOURS: [Color; 4] = [
+    Color {
+        r: 0x35 as f32 / 255.0,
+        g: 0x79 as f32 / 255.0,
+        b: 0x60 as f32 / 255.0,
+        a: 1.0,
+    },
+    Color::BLACK,
+    Color::WHITE,
+    Color {
+        r: 1.0,
+        g: 0.0,
+        b: 1.0,
+        a: 1.0,
+    },
+];
+/// Light and dark squares of the checkerboard under empty cells
+const CHECKER_COLOURS: (Color, Color) = (
+    Color::from_rgb(0.8, 0.8, 0.8),
+    Color::from_rgb(0.6, 0.6, 0.6),
+);
 const BORDER_SIZE: f32 = 1.0;
+const CHUNK_BORDER_SIZE: f32 = 2.0;
+const CHUNK_COLOUR: Color = Color::from_rgb(1.0, 0.8, 0.2);
+
+/// Dims the map while there is no tilesheet
+const OVERLAY_COLOUR: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+    a: 0.5,
+};
+
+const SELECTION_COLOUR: Color = Color {
+    r: 0.2,
+    g: 0.5,
+    b: 1.0,
+    a: 0.4,
+};
+
+/// Cells differing from the compared map
+const DIFF_COLOUR: Color = Color {
+    r: 1.0,
+    g: 0.1,
+    b: 0.4,
+    a: 0.4,
+};
 
 #[derive(Default, Debug)]
 pub struct ViewerState {
     interaction: Interaction,
     rect_dimensions: (i32, i32),
+    hovered: Option<(u16, u16)>,
+    /// Origin of the last stamp of the stroke
+    last_stamp: Option<(i32, i32)>,
+    modifiers: keyboard::Modifiers,
 }
 
 #[derive(Default, Debug)]
@@ -110,6 +1030,15 @@ enum Interaction {
     None,
     Drawing,
     Rectangle(u16, u16),
+    RectOutline(u16, u16),
+    Ellipse(u16, u16),
+    Selecting(u16, u16),
+    /// Selection grabbed at `origin`, and moved by `offset` cells
+    MovingSelection {
+        origin: (u16, u16),
+        offset: (i32, i32),
+    },
+    Stamping(u16, u16),
     Erasing,
 }
 
@@ -123,32 +1052,78 @@ impl canvas::Program<Message> for MapViewer {
         bounds: iced::Rectangle,
         cursor: iced::canvas::Cursor,
     ) -> (iced::canvas::event::Status, Option<Message>) {
-        let (x, y) = if let Some(position) = cursor.position_in(&bounds) {
-            let tile_side = 8.0 * SCALE_FACTOR + BORDER_SIZE;
-            (
-                (position.x / tile_side).floor() as u16,
-                (position.y / tile_side).floor() as u16,
-            )
+        if let Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) = event {
+            state.modifiers = modifiers;
+            return (Status::Ignored, None);
+        }
+
+        // zooming works outside of the map too
+        if let Event::Mouse(mouse::Event::WheelScrolled { delta }) = event {
+            let lines = match delta {
+                mouse::ScrollDelta::Lines { y, .. } => y,
+                mouse::ScrollDelta::Pixels { y, .. } => y / PIXELS_PER_LINE,
+            };
+            return match cursor.position_in(&bounds) {
+                Some(position) if lines != 0.0 => (
+                    Status::Captured,
+                    Some(Message::ZoomMap(ZOOM_STEP.powf(lines), position)),
+                ),
+                _ => (Status::Ignored, None),
+            };
+        }
+
+        let (x, y) = if let Some(tile) = self.hovered_tile(bounds, cursor) {
+            tile
         } else {
+            if state.hovered.take().is_some() {
+                return (Status::Ignored, Some(Message::CursorLeftMap));
+            }
             return (Status::Ignored, None);
         };
+        let hover_changed = state.hovered.replace((x, y)) != Some((x, y));
 
         match event {
             Event::Mouse(event) => match event {
                 mouse::Event::ButtonReleased(_) => {
+                    let (width, height) = state.rect_dimensions;
                     match state.interaction {
                         Interaction::Rectangle(x, y) => {
                             state.interaction = Interaction::None;
                             return (
                                 Status::Captured,
-                                Some(Message::PaintRect(
-                                    x,
-                                    y,
-                                    state.rect_dimensions.0,
-                                    state.rect_dimensions.1,
-                                )),
+                                Some(Message::PaintRect(x, y, width, height)),
+                            );
+                        }
+                        Interaction::RectOutline(x, y) => {
+                            state.interaction = Interaction::None;
+                            return (
+                                Status::Captured,
+                                Some(Message::PaintRectOutline(x, y, width, height)),
                             );
                         }
+                        Interaction::Ellipse(x, y) => {
+                            state.interaction = Interaction::None;
+                            return (
+                                Status::Captured,
+                                Some(Message::PaintEllipse(x, y, width, height)),
+                            );
+                        }
+                        Interaction::Selecting(x, y) => {
+                            state.interaction = Interaction::None;
+                            return (
+                                Status::Captured,
+                                Some(Message::SelectRect(x, y, width, height)),
+                            );
+                        }
+                        Interaction::MovingSelection { offset, .. } => {
+                            state.interaction = Interaction::None;
+                            let message = if offset == (0, 0) {
+                                Message::Redraw
+                            } else {
+                                Message::MoveSelection(offset.0, offset.1)
+                            };
+                            return (Status::Captured, Some(message));
+                        }
                         _ => {}
                     }
 
@@ -156,16 +1131,66 @@ impl canvas::Program<Message> for MapViewer {
                 }
                 mouse::Event::ButtonPressed(button) => match button {
                     mouse::Button::Left => match self.tool {
-                        Tool::Pen => {
+                        Tool::Pen | Tool::Scatter => {
                             state.interaction = Interaction::Drawing;
                             return (Status::Captured, Some(Message::PaintTile(x, y)));
                         }
+                        Tool::Eraser => {
+                            state.interaction = Interaction::Erasing;
+                            return (Status::Captured, Some(Message::ClearTile(x, y)));
+                        }
                         Tool::Rect => {
                             state.interaction = Interaction::Rectangle(x, y);
                             state.rect_dimensions = (1, 1);
                             return (Status::Captured, Some(Message::RectStarted));
                         }
-                        _ => {}
+                        Tool::RectOutline => {
+                            state.interaction = Interaction::RectOutline(x, y);
+                            state.rect_dimensions = (1, 1);
+                            return (Status::Captured, Some(Message::RectStarted));
+                        }
+                        Tool::Ellipse => {
+                            state.interaction = Interaction::Ellipse(x, y);
+                            state.rect_dimensions = (1, 1);
+                            return (Status::Captured, Some(Message::RectStarted));
+                        }
+                        Tool::Picker => {
+                            return (Status::Captured, Some(Message::PickTile(x, y)));
+                        }
+                        // with shift, the wand ignores connectivity
+                        Tool::MagicWand if state.modifiers.shift() => {
+                            return (Status::Captured, Some(Message::SelectMatching(x, y)));
+                        }
+                        // with control, it takes the empty room bounded by any tile
+                        Tool::MagicWand if state.modifiers.control() => {
+                            return (Status::Captured, Some(Message::SelectEnclosed(x, y)));
+                        }
+                        Tool::MagicWand => {
+                            return (Status::Captured, Some(Message::SelectRegion(x, y)));
+                        }
+                        // grabbing the selection moves it
+                        Tool::Selection if self.selection.contains(&(x, y)) => {
+                            state.interaction = Interaction::MovingSelection {
+                                origin: (x, y),
+                                offset: (0, 0),
+                            };
+                            return (Status::Captured, Some(Message::Redraw));
+                        }
+                        Tool::Selection => {
+                            state.interaction = Interaction::Selecting(x, y);
+                            state.rect_dimensions = (1, 1);
+                            return (Status::Captured, Some(Message::Redraw));
+                        }
+                        Tool::Stamp => {
+                            if let Some((origin_x, origin_y)) = self.stamp_origin(x, y, x, y) {
+                                state.interaction = Interaction::Stamping(x, y);
+                                state.last_stamp = Some((origin_x, origin_y));
+                                return (
+                                    Status::Captured,
+                                    Some(Message::StampAt(origin_x, origin_y)),
+                                );
+                            }
+                        }
                     },
                     mouse::Button::Right => {
                         state.interaction = Interaction::Erasing;
@@ -174,13 +1199,44 @@ impl canvas::Program<Message> for MapViewer {
                     _ => {}
                 },
                 mouse::Event::CursorMoved { .. } => match state.interaction {
+                    // moving within a cell already painted would only redraw the map
+                    Interaction::Drawing | Interaction::Erasing if !hover_changed => {
+                        return (Status::Captured, None)
+                    }
                     Interaction::Drawing => {
                         return (Status::Captured, Some(Message::PaintTile(x, y)))
                     }
                     Interaction::Erasing => {
                         return (Status::Captured, Some(Message::ClearTile(x, y)))
                     }
-                    Interaction::Rectangle(rect_x, rect_y) => {
+                    Interaction::MovingSelection {
+                        origin: (origin_x, origin_y),
+                        offset,
+                    } => {
+                        let new_offset = (x as i32 - origin_x as i32, y as i32 - origin_y as i32);
+                        if new_offset != offset {
+                            state.interaction = Interaction::MovingSelection {
+                                origin: (origin_x, origin_y),
+                                offset: new_offset,
+                            };
+                            return (Status::Captured, Some(Message::Redraw));
+                        }
+                    }
+                    Interaction::Stamping(start_x, start_y) => {
+                        let origin = self.stamp_origin(start_x, start_y, x, y);
+                        if let Some((origin_x, origin_y)) = origin {
+                            if state.last_stamp.replace((origin_x, origin_y)) != origin {
+                                return (
+                                    Status::Captured,
+                                    Some(Message::StampAt(origin_x, origin_y)),
+                                );
+                            }
+                        }
+                    }
+                    Interaction::Rectangle(rect_x, rect_y)
+                    | Interaction::RectOutline(rect_x, rect_y)
+                    | Interaction::Ellipse(rect_x, rect_y)
+                    | Interaction::Selecting(rect_x, rect_y) => {
                         let length = |a: u16, b: u16| {
                             let sub = a as i32 - b as i32;
                             sub + if sub >= 0 { 1 } else { 0 }
@@ -192,7 +1248,11 @@ impl canvas::Program<Message> for MapViewer {
                         state.rect_dimensions = (new_width, new_height);
                         return (Status::Captured, Some(Message::Redraw));
                     }
-                    _ => {}
+                    _ => {
+                        if hover_changed {
+                            return (Status::Ignored, Some(Message::CursorMovedTile(x, y)));
+                        }
+                    }
                 },
 
                 _ => {}
@@ -207,44 +1267,107 @@ impl canvas::Program<Message> for MapViewer {
         &self,
         state: &Self::State,
         bounds: iced::Rectangle,
-        _cursor: iced::canvas::Cursor,
+        cursor: iced::canvas::Cursor,
     ) -> Vec<iced::canvas::Geometry> {
-        let map_view = self.cache.draw(bounds.size(), |frame| {
-            let (width, height) = self.map.get_dimensions();
+        self.bounds.set(bounds.size());
 
-            let default_colour = Color::new(
-                0x35 as f32 / 255.0,
-                0x79 as f32 / 255.0,
-                0x60 as f32 / 255.0,
-                1.0,
-            );
+        let map_view = self.cache.draw(bounds.size(), |frame| {
+            let (columns, rows) = self.visible_tiles(bounds.size());
+            frame.translate(self.pixel_translation());
 
-            let border_colour = Color::new(0.7, 0.7, 0.7, 1.0);
+            let tile_side = self.tile_side();
 
-            let tile_side = 8.0 * SCALE_FACTOR + BORDER_SIZE;
+            // extent of the visible part of the map
+            let visible_width = columns.end as f32 * tile_side;
+            let visible_height = rows.end as f32 * tile_side;
 
             // fill base colour
             frame.fill_rectangle(
                 Point::new(0.0, 0.0),
-                Size::new(width as f32 * tile_side, height as f32 * tile_side),
-                default_colour,
+                Size::new(visible_width, visible_height),
+                if self.checkerboard {
+                    CHECKER_COLOURS.0
+                } else {
+                    self.empty_colour
+                },
             );
 
+            // the dark squares, two per cell
+            if self.checkerboard {
+                let half = tile_side / 2.0;
+                let squares = Path::new(|builder| {
+                    for y in rows.clone() {
+                        for x in columns.clone() {
+                            let corner = Point::new(x as f32 * tile_side, y as f32 * tile_side);
+                            builder.rectangle(corner, Size::new(half, half));
+                            builder
+                                .rectangle(corner + Vector::new(half, half), Size::new(half, half));
+                        }
+                    }
+                });
+                frame.fill(&squares, CHECKER_COLOURS.1);
+            }
+
+            if let Some(reference) = self.reference.as_ref().filter(|r| r.visible) {
+                let area = Rectangle::new(
+                    Point::new(
+                        columns.start as f32 * tile_side,
+                        rows.start as f32 * tile_side,
+                    ),
+                    Size::new(
+                        columns.len() as f32 * tile_side,
+                        rows.len() as f32 * tile_side,
+                    ),
+                );
+                reference.draw(frame, area, tile_side, self.tile_size);
+            }
+
             // fill tiles
-            if let Some(tiles) = &*self.tiles.borrow() {
-                // draw tiles
-                for y in 0..height {
-                    for x in 0..width {
-                        let (bg_tile, fg_tile) = self.map.get_tile(x, y);
-
-                        // draw background first
-                        if let Some(tile) = bg_tile {
-                            draw_tile(tile, x, y, frame, tiles, false);
+            let sheets = self.tiles.borrow();
+            if !sheets.is_empty() {
+                let tiles = &sheets[..];
+
+                // the selection being moved is drawn at its new place only
+                let moving = match state.interaction {
+                    Interaction::MovingSelection { offset, .. } => Some(offset),
+                    _ => None,
+                };
+
+                // draw layers from the bottom
+                for layer in 0..self.map.layer_count() {
+                    let mut opacity = self.opacity(layer);
+                    if layer > self.layer {
+                        // layers above are dimmed so that the active one is visible below
+                        opacity *= 0.5;
+                    }
+
+                    for y in rows.clone() {
+                        for x in columns.clone() {
+                            if moving.is_some()
+                                && layer == self.layer
+                                && self.selection.contains(&(x, y))
+                            {
+                                continue;
+                            }
+                            if let Some(tile) = self.map.get_layer_tile(x, y, layer) {
+                                self.draw_tile(tile, x, y, frame, tiles, opacity);
+                            }
                         }
+                    }
+                }
 
-                        // then draw foreground above
-                        if let Some(tile) = fg_tile {
-                            draw_tile(tile, x, y, frame, tiles, self.layer == Layer::Background);
+                // draw preview move ?
+                if let Some((dx, dy)) = moving {
+                    for &(x, y) in &self.selection {
+                        let (new_x, new_y) = (x as i32 + dx, y as i32 + dy);
+                        if let (Some(tile), Ok(new_x), Ok(new_y)) = (
+                            self.map.get_layer_tile(x, y, self.layer),
+                            u16::try_from(new_x),
+                            u16::try_from(new_y),
+                        ) {
+                            if columns.contains(&new_x) && rows.contains(&new_y) {
+                                self.draw_tile(tile, new_x, new_y, frame, tiles, 1.0);
+                            }
                         }
                     }
                 }
@@ -259,69 +1382,423 @@ impl canvas::Program<Message> for MapViewer {
                     for x in min_x..(min_x + width.abs()) {
                         for y in min_y..(min_y + height.abs()) {
                             if let Some(tile) = self.tile {
-                                draw_tile(tile, x as u16, y as u16, frame, tiles, false);
+                                self.draw_preview(tile, x as u16, y as u16, frame, tiles);
                             }
                         }
                     }
                 }
+
+                // draw preview outline ?
+                if let Interaction::RectOutline(x_rect, y_rect) = state.interaction {
+                    let (width, height) = state.rect_dimensions;
+
+                    if let Some(tile) = self.tile {
+                        for (x, y) in rect_outline_cells(x_rect, y_rect, width, height) {
+                            self.draw_preview(tile, x as u16, y as u16, frame, tiles);
+                        }
+                    }
+                }
+
+                // draw preview ellipse ?
+                if let Interaction::Ellipse(x_rect, y_rect) = state.interaction {
+                    let (width, height) = state.rect_dimensions;
+
+                    if let Some(tile) = self.tile {
+                        for (x, y) in ellipse_cells(x_rect, y_rect, width, height) {
+                            self.draw_preview(tile, x as u16, y as u16, frame, tiles);
+                        }
+                    }
+                }
             }
 
-            // draw grid
-            // vertical lines
-            for line in 0..width {
-                frame.fill_rectangle(
-                    Point::new(line as f32 * tile_side, 0.0),
-                    Size::new(BORDER_SIZE, height as f32 * tile_side),
-                    border_colour,
-                )
+            // cells the compared map has other tiles in
+            if let Some(compared) = &self.compared {
+                let differing: HashSet<_> = self
+                    .map
+                    .diff(compared)
+                    .into_iter()
+                    .map(|(x, y, _)| (x, y))
+                    .filter(|(x, y)| columns.contains(x) && rows.contains(y))
+                    .collect();
+                for (x, y) in differing {
+                    frame.fill_rectangle(
+                        Point::new(x as f32 * tile_side, y as f32 * tile_side),
+                        Size::new(tile_side, tile_side),
+                        DIFF_COLOUR,
+                    );
+                }
             }
 
-            // horizontal rows
-            for row in 0..height {
+            // highlight the selection, where it is being moved to
+            let (dx, dy) = match state.interaction {
+                Interaction::MovingSelection { offset, .. } => offset,
+                _ => (0, 0),
+            };
+            for &(x, y) in &self.selection {
+                let (x, y) = (x as i32 + dx, y as i32 + dy);
+                if (columns.start as i32..columns.end as i32).contains(&x)
+                    && (rows.start as i32..rows.end as i32).contains(&y)
+                {
+                    frame.fill_rectangle(
+                        Point::new(x as f32 * tile_side, y as f32 * tile_side),
+                        Size::new(tile_side, tile_side),
+                        SELECTION_COLOUR,
+                    );
+                }
+            }
+
+            // and the one being drawn
+            if let Interaction::Selecting(x_rect, y_rect) = state.interaction {
+                let (width, height) = state.rect_dimensions;
+                let min_x = i32::min(x_rect as i32, x_rect as i32 + width);
+                let min_y = i32::min(y_rect as i32, y_rect as i32 + height);
+
                 frame.fill_rectangle(
-                    Point::new(0.0, row as f32 * tile_side),
-                    Size::new(width as f32 * tile_side, BORDER_SIZE),
-                    border_colour,
-                )
+                    Point::new(min_x as f32 * tile_side, min_y as f32 * tile_side),
+                    Size::new(
+                        width.abs() as f32 * tile_side,
+                        height.abs() as f32 * tile_side,
+                    ),
+                    SELECTION_COLOUR,
+                );
+            }
+
+            // draw grid
+            if self.show_grid {
+                let lines = grid_lines(
+                    (columns.clone(), rows.clone()),
+                    self.map.get_dimensions(),
+                    tile_side,
+                );
+                for line in lines {
+                    frame.fill_rectangle(line.position(), line.size(), self.grid_colour);
+                }
+            }
+
+            // chunk guides, over the grid
+            if let Some(chunk_size) = self.chunk_size {
+                for line in columns.filter(|line| line % chunk_size == 0) {
+                    frame.fill_rectangle(
+                        Point::new(line as f32 * tile_side, 0.0),
+                        Size::new(CHUNK_BORDER_SIZE, visible_height),
+                        CHUNK_COLOUR,
+                    )
+                }
+                for row in rows.filter(|row| row % chunk_size == 0) {
+                    frame.fill_rectangle(
+                        Point::new(0.0, row as f32 * tile_side),
+                        Size::new(visible_width, CHUNK_BORDER_SIZE),
+                        CHUNK_COLOUR,
+                    )
+                }
             }
         });
-        vec![map_view]
+
+        // the highlight follows the cursor, so it is redrawn every time
+        let mut hover = Frame::new(bounds.size());
+        hover.translate(self.pixel_translation());
+        if let Some((x, y)) = self.hovered_tile(bounds, cursor) {
+            let tile_side = self.tile_side();
+            let (columns, rows) = self.brush_extent(x, y);
+            hover.stroke(
+                &Path::rectangle(
+                    Point::new(
+                        columns.start as f32 * tile_side + BORDER_SIZE / 2.0,
+                        rows.start as f32 * tile_side + BORDER_SIZE / 2.0,
+                    ),
+                    Size::new(
+                        columns.len() as f32 * tile_side,
+                        rows.len() as f32 * tile_side,
+                    ),
+                ),
+                Stroke::default()
+                    .with_width(BORDER_SIZE)
+                    .with_color(Color::WHITE),
+            );
+
+            // a faint cross where the eraser would clear
+            if self.tool == Tool::Eraser {
+                let (left, top) = (x as f32 * tile_side, y as f32 * tile_side);
+                let (right, bottom) = (left + tile_side, top + tile_side);
+                let cross = Path::new(|path| {
+                    path.move_to(Point::new(left, top));
+                    path.line_to(Point::new(right, bottom));
+                    path.move_to(Point::new(right, top));
+                    path.line_to(Point::new(left, bottom));
+                });
+                hover.stroke(
+                    &cross,
+                    Stroke::default()
+                        .with_width(BORDER_SIZE)
+                        .with_color(Color::from_rgba(1.0, 1.0, 1.0, 0.5)),
+                );
+            }
+        }
+
+        // the copied tiles, as the stamp would paint them
+        if let (Tool::Stamp, Some(clipboard), Some((x, y))) = (
+            self.tool,
+            &self.clipboard,
+            self.hovered_tile(bounds, cursor),
+        ) {
+            let (width, height) = self.map.get_dimensions();
+            let (origin_x, origin_y) = match state.interaction {
+                Interaction::Stamping(start_x, start_y) => {
+                    self.stamp_origin(start_x, start_y, x, y)
+                }
+                _ => self.stamp_origin(x, y, x, y),
+            }
+            .unwrap_or((x as i32, y as i32));
+
+            let sheets = self.tiles.borrow();
+            for (dx, column) in clipboard.tiles.iter().enumerate() {
+                for (dy, tile) in column.iter().enumerate() {
+                    let (x, y) = (origin_x + dx as i32, origin_y + dy as i32);
+                    if let (Some(tile), Ok(x), Ok(y)) = (tile, u16::try_from(x), u16::try_from(y)) {
+                        if x < width && y < height {
+                            self.draw_tile(*tile, x, y, &mut hover, &sheets, 0.5);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut geometry = vec![map_view, hover.into_geometry()];
+
+        // nothing can be painted before a tilesheet is loaded
+        if self.tiles.borrow().is_empty() {
+            let mut overlay = Frame::new(bounds.size());
+            overlay.fill_rectangle(Point::ORIGIN, bounds.size(), OVERLAY_COLOUR);
+            overlay.fill_text(Text {
+                content: "Load a tilesheet to begin".to_string(),
+                position: overlay.center(),
+                color: Color::WHITE,
+                size: 24.0,
+                horizontal_alignment: alignment::Horizontal::Center,
+                vertical_alignment: alignment::Vertical::Center,
+                ..Default::default()
+            });
+            geometry.push(overlay.into_geometry());
+        }
+
+        geometry
     }
 }
 
-fn draw_tile(
-    tile: Tile,
-    x: u16,
-    y: u16,
-    frame: &mut Frame,
-    tiles: &AsepriteFile,
-    transparent: bool,
-) {
-    if tile.value < tiles.num_frames() {
-        let base_x = x as f32 * (8.0 * SCALE_FACTOR + BORDER_SIZE);
-        let base_y = y as f32 * (8.0 * SCALE_FACTOR + BORDER_SIZE);
-
-        // this is a valid index for the current tiles
-        for (idx, pixel) in tiles
-            .frame(tile.value)
-            .image()
-            .pixels()
-            .take(64)
-            .enumerate()
-        {
-            let x = if tile.h_flip { 7 - (idx % 8) } else { idx % 8 } as f32;
-            let y = if tile.v_flip { (64 - idx) / 8 } else { idx / 8 } as f32;
+/// Lines of the grid over the visible `columns` and `rows` of a map of size `dimensions`.
+///
+/// Each cell gets a line on its left and top side, the last column and row of the map also
+/// getting closing lines, on their last pixels so that they stay inside the map.
+fn grid_lines(
+    (columns, rows): (Range<u16>, Range<u16>),
+    (width, height): (u16, u16),
+    tile_side: f32,
+) -> Vec<Rectangle> {
+    let visible_width = columns.end as f32 * tile_side;
+    let visible_height = rows.end as f32 * tile_side;
+    let vertical =
+        |x: f32| Rectangle::new(Point::new(x, 0.0), Size::new(BORDER_SIZE, visible_height));
+    let horizontal =
+        |y: f32| Rectangle::new(Point::new(0.0, y), Size::new(visible_width, BORDER_SIZE));
 
-            frame.fill_rectangle(
-                Point::new(base_x + x * SCALE_FACTOR, base_y + y * SCALE_FACTOR),
-                Size::new(SCALE_FACTOR, SCALE_FACTOR),
-                Color::new(
-                    pixel.0[0] as f32 / 255.0,
-                    pixel.0[1] as f32 / 255.0,
-                    pixel.0[2] as f32 / 255.0,
-                    if transparent { 0.5 } else { 1.0 },
-                ),
-            )
+    let mut lines = Vec::new();
+    if columns.end == width {
+        lines.push(vertical(visible_width - BORDER_SIZE));
+    }
+    if rows.end == height {
+        lines.push(horizontal(visible_height - BORDER_SIZE));
+    }
+    lines.extend(columns.map(|line| vertical(line as f32 * tile_side)));
+    lines.extend(rows.map(|row| horizontal(row as f32 * tile_side)));
+
+    lines
+}
+
+/// Cells on the border of the rectangle starting at `(x, y)`, its dimensions being negative
+/// when it extends up or left
+fn rect_outline_cells(x: u16, y: u16, width: i32, height: i32) -> Vec<(i32, i32)> {
+    let min_x = i32::min(x as i32, x as i32 + width);
+    let min_y = i32::min(y as i32, y as i32 + height);
+    let (max_x, max_y) = (min_x + width.abs() - 1, min_y + height.abs() - 1);
+
+    let mut cells = Vec::new();
+    for x in min_x..=max_x {
+        for y in min_y..=max_y {
+            if x == min_x || x == max_x || y == min_y || y == max_y {
+                cells.push((x, y));
+            }
         }
     }
+
+    cells
+}
+
+/// Cells on the outline of the ellipse inscribed in the rectangle starting at `(x, y)`
+///
+/// `width` and `height` may be negative, like for rectangles.
+fn ellipse_cells(x: u16, y: u16, width: i32, height: i32) -> Vec<(i32, i32)> {
+    let min_x = i32::min(x as i32, x as i32 + width);
+    let min_y = i32::min(y as i32, y as i32 + height);
+    let (width, height) = (width.abs(), height.abs());
+
+    let radius_x = width as f32 / 2.0;
+    let radius_y = height as f32 / 2.0;
+    let centre_x = min_x as f32 + radius_x;
+    let centre_y = min_y as f32 + radius_y;
+
+    // whether the centre of the cell lies in the ellipse
+    let inside = |x: i32, y: i32| {
+        let dx = (x as f32 + 0.5 - centre_x) / radius_x;
+        let dy = (y as f32 + 0.5 - centre_y) / radius_y;
+        dx * dx + dy * dy <= 1.0
+    };
+
+    let mut cells = Vec::new();
+    for x in min_x..(min_x + width) {
+        for y in min_y..(min_y + height) {
+            // keep only the cells of the disk touching its outside
+            if inside(x, y)
+                && !(inside(x - 1, y) && inside(x + 1, y) && inside(x, y - 1) && inside(x, y + 1))
+            {
+                cells.push((x, y));
+            }
+        }
+    }
+
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_lines_close_the_last_cells_inside_the_map() {
+        let (width, height) = (3, 2);
+        let tile_side = 17.0;
+        let lines = grid_lines((0..width, 0..height), (width, height), tile_side);
+
+        let extent = Rectangle::new(
+            Point::ORIGIN,
+            Size::new(width as f32 * tile_side, height as f32 * tile_side),
+        );
+        for line in &lines {
+            assert!(
+                line.x >= extent.x
+                    && line.y >= extent.y
+                    && line.x + line.width <= extent.x + extent.width
+                    && line.y + line.height <= extent.y + extent.height,
+                "{line:?} out of {extent:?}"
+            );
+        }
+
+        // a line along each side of the map
+        let side = |x: f32, y: f32, line_width: f32, line_height: f32| {
+            lines.contains(&Rectangle::new(
+                Point::new(x, y),
+                Size::new(line_width, line_height),
+            ))
+        };
+        assert!(side(0.0, 0.0, BORDER_SIZE, extent.height));
+        assert!(side(0.0, 0.0, extent.width, BORDER_SIZE));
+        assert!(side(
+            extent.width - BORDER_SIZE,
+            0.0,
+            BORDER_SIZE,
+            extent.height
+        ));
+        assert!(side(
+            0.0,
+            extent.height - BORDER_SIZE,
+            extent.width,
+            BORDER_SIZE
+        ));
+    }
+
+    #[test]
+    fn grid_lines_are_not_closed_before_the_end_of_the_map() {
+        let tile_side = 17.0;
+        let lines = grid_lines((0..2, 0..1), (3, 2), tile_side);
+
+        // one line per visible column and row, none closing them
+        assert_eq!(lines.len(), 3);
+        assert!(
+            lines
+                .iter()
+                .all(|line| line.x.rem_euclid(tile_side) == 0.0
+                    && line.y.rem_euclid(tile_side) == 0.0)
+        );
+    }
+
+    /// Viewer of a `width` by `height` map, whose active layer is full of the tile 1
+    fn filled_viewer(width: u16, height: u16) -> MapViewer {
+        let mut viewer = MapViewer::new(Default::default());
+        viewer.map = TileMap::new(width, height);
+        viewer.map.fill_layer(Some(Tile::new(1, false, false)), 0);
+        viewer
+    }
+
+    #[test]
+    fn deleting_the_selection_only_empties_its_cells() {
+        let mut viewer = filled_viewer(3, 3);
+        viewer.fill_selection(None);
+        assert!(!viewer.modified, "nothing is selected");
+
+        viewer.select_rect(1, 1, 2, 1);
+        viewer.fill_selection(None);
+
+        assert!(viewer.modified);
+        for y in 0..3 {
+            for x in 0..3 {
+                let deleted = y == 1 && x >= 1;
+                assert_eq!(
+                    viewer.map.get_layer_tile(x, y, 0).is_none(),
+                    deleted,
+                    "({x}, {y})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn copying_a_layer_modifies_the_map() {
+        let mut viewer = filled_viewer(2, 2);
+        viewer.copy_layer(0, 1);
+
+        assert!(viewer.modified);
+        assert_eq!(
+            viewer.map.get_layer_tile(1, 1, 1),
+            Some(Tile::new(1, false, false))
+        );
+    }
+
+    #[test]
+    fn swapping_layers_keeps_the_active_one() {
+        let mut viewer = filled_viewer(2, 2);
+        viewer.opacity = vec![1.0, 0.5];
+        viewer.swap_layers(0, 1);
+
+        assert!(viewer.modified);
+        assert_eq!(viewer.layer, 1);
+        assert_eq!(viewer.opacity, vec![0.5, 1.0]);
+        assert_eq!(
+            viewer.map.get_layer_tile(0, 0, 1),
+            Some(Tile::new(1, false, false))
+        );
+        assert_eq!(viewer.map.get_layer_tile(0, 0, 0), None);
+    }
+
+    #[test]
+    fn setting_an_identical_tile_changes_nothing() {
+        let mut viewer = filled_viewer(2, 2);
+        viewer.set_tile(0, 0, Some(Tile::new(1, false, false)));
+        assert!(!viewer.modified, "painting a tile over itself");
+
+        viewer.layer = 1;
+        viewer.set_tile(0, 0, None);
+        assert!(!viewer.modified, "erasing an empty cell");
+
+        viewer.set_tile(0, 0, Some(Tile::new(1, true, false)));
+        assert!(viewer.modified, "painting a flipped tile");
+    }
 }
@@ -0,0 +1,68 @@
+use iced::{
+    pure::{
+        text_input,
+        widget::{Button, Row, Text},
+        Element,
+    },
+    Alignment, Length,
+};
+
+use crate::{tilemap::MapMetadata, Message};
+
+/// Inputs of the map properties, shown below the toolbar while open
+pub struct MetadataDialog {
+    metadata: MapMetadata,
+}
+
+#[derive(Debug, Clone)]
+pub enum MetadataInput {
+    Name(String),
+    Author(String),
+    Description(String),
+}
+
+impl MetadataDialog {
+    /// Dialog editing `metadata`, the one of the current map
+    pub fn new(metadata: MapMetadata) -> Self {
+        MetadataDialog { metadata }
+    }
+
+    pub fn update(&mut self, input: MetadataInput) {
+        match input {
+            MetadataInput::Name(name) => self.metadata.name = name,
+            MetadataInput::Author(author) => self.metadata.author = author,
+            MetadataInput::Description(description) => self.metadata.description = description,
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        Row::new()
+            .spacing(5)
+            .align_items(Alignment::Center)
+            .push(Text::new("Map"))
+            .push(
+                text_input("name", &self.metadata.name, |name| {
+                    Message::MetadataInput(MetadataInput::Name(name))
+                })
+                .width(Length::Units(120)),
+            )
+            .push(Text::new("by"))
+            .push(
+                text_input("author", &self.metadata.author, |author| {
+                    Message::MetadataInput(MetadataInput::Author(author))
+                })
+                .width(Length::Units(120)),
+            )
+            .push(text_input(
+                "description",
+                &self.metadata.description,
+                |description| Message::MetadataInput(MetadataInput::Description(description)),
+            ))
+            .push(
+                Button::new(Text::new("Apply"))
+                    .on_press(Message::MetadataChanged(self.metadata.clone())),
+            )
+            .push(Button::new(Text::new("Cancel")).on_press(Message::MetadataDialogClosed))
+            .into()
+    }
+}
@@ -0,0 +1,169 @@
+//! Overview of the whole map, to find one's way around large maps
+
+use std::ops::Range;
+
+use iced::{
+    canvas::{event::Status, Cursor, Event, Frame, Path, Stroke},
+    mouse,
+    pure::{
+        widget::{canvas, Canvas},
+        Element,
+    },
+    Color, Length, Point, Rectangle, Size,
+};
+
+use crate::{tilemap::TileMap, Message};
+
+/// Largest side of the minimap, unless the map has more tiles than pixels
+const MAX_SIDE: f32 = 180.0;
+
+const FILLED_COLOUR: Color = Color::from_rgb(0.35, 0.35, 0.35);
+const EMPTY_COLOUR: Color = Color::from_rgb(0.9, 0.9, 0.9);
+
+#[derive(Default)]
+pub struct MiniMap {
+    cache: canvas::Cache,
+}
+
+impl MiniMap {
+    /// Forces redrawing, to be called whenever the map changes
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// View of `map`, the part of it shown by the viewer, `viewport`, being outlined
+    pub fn view<'a>(
+        &'a self,
+        map: &'a TileMap,
+        viewport: (Range<u16>, Range<u16>),
+    ) -> Element<'a, Message> {
+        let (width, height) = map.get_dimensions();
+        let cell = cell_side(width, height);
+
+        Canvas::new(Overview {
+            minimap: self,
+            map,
+            viewport,
+            cell,
+        })
+        .width(Length::Units((width as f32 * cell).ceil() as u16))
+        .height(Length::Units((height as f32 * cell).ceil() as u16))
+        .into()
+    }
+}
+
+/// Side of a tile in the minimap, at least one pixel
+fn cell_side(width: u16, height: u16) -> f32 {
+    (MAX_SIDE / width.max(height).max(1) as f32)
+        .floor()
+        .max(1.0)
+}
+
+/// Minimap of a given map, built for each view
+struct Overview<'a> {
+    minimap: &'a MiniMap,
+    map: &'a TileMap,
+    viewport: (Range<u16>, Range<u16>),
+    cell: f32,
+}
+
+impl Overview<'_> {
+    fn tile_at(&self, bounds: Rectangle, cursor: Cursor) -> Option<(u16, u16)> {
+        let position = cursor.position_in(&bounds)?;
+        let (width, height) = self.map.get_dimensions();
+
+        let x = (position.x / self.cell) as u16;
+        let y = (position.y / self.cell) as u16;
+        (x < width && y < height).then_some((x, y))
+    }
+}
+
+impl canvas::Program<Message> for Overview<'_> {
+    /// Whether the viewport is being dragged
+    type State = bool;
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: Event,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> (Status, Option<Message>) {
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                match self.tile_at(bounds, cursor) {
+                    Some((x, y)) => {
+                        *state = true;
+                        (Status::Captured, Some(Message::JumpTo(x, y)))
+                    }
+                    None => (Status::Ignored, None),
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) if *state => {
+                match self.tile_at(bounds, cursor) {
+                    Some((x, y)) => (Status::Captured, Some(Message::JumpTo(x, y))),
+                    None => (Status::Captured, None),
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                *state = false;
+                (Status::Ignored, None)
+            }
+            _ => (Status::Ignored, None),
+        }
+    }
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        bounds: Rectangle,
+        _cursor: Cursor,
+    ) -> Vec<canvas::Geometry> {
+        let overview = self.minimap.cache.draw(bounds.size(), |frame| {
+            let (width, height) = self.map.get_dimensions();
+            frame.fill_rectangle(
+                Point::ORIGIN,
+                Size::new(width as f32 * self.cell, height as f32 * self.cell),
+                EMPTY_COLOUR,
+            );
+
+            for x in 0..width {
+                for y in 0..height {
+                    let filled = (0..self.map.layer_count())
+                        .any(|layer| self.map.get_layer_tile(x, y, layer).is_some());
+
+                    if filled {
+                        frame.fill_rectangle(
+                            Point::new(x as f32 * self.cell, y as f32 * self.cell),
+                            Size::new(self.cell, self.cell),
+                            FILLED_COLOUR,
+                        );
+                    }
+                }
+            }
+        });
+
+        // the viewport moves without the map changing, so it is redrawn every time
+        let mut viewport = Frame::new(bounds.size());
+        let (columns, rows) = &self.viewport;
+        if !columns.is_empty() && !rows.is_empty() {
+            viewport.stroke(
+                &Path::rectangle(
+                    Point::new(
+                        columns.start as f32 * self.cell,
+                        rows.start as f32 * self.cell,
+                    ),
+                    Size::new(
+                        columns.len() as f32 * self.cell,
+                        rows.len() as f32 * self.cell,
+                    ),
+                ),
+                Stroke::default()
+                    .with_width(1.0)
+                    .with_color(Color::from_rgb(1.0, 0.0, 0.0)),
+            );
+        }
+
+        vec![overview, viewport.into_geometry()]
+    }
+}
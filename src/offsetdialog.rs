@@ -0,0 +1,76 @@
+use iced::{
+    pure::{
+        checkbox, text_input,
+        widget::{Button, Row, Text},
+        Element,
+    },
+    Alignment, Length,
+};
+
+use crate::Message;
+
+/// Inputs of the layer offset, shown below the toolbar while open
+#[derive(Default)]
+pub struct OffsetDialog {
+    dx: String,
+    dy: String,
+    wrap: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum OffsetInput {
+    Dx(String),
+    Dy(String),
+    Wrap(bool),
+}
+
+impl OffsetDialog {
+    pub fn update(&mut self, input: OffsetInput) {
+        match input {
+            OffsetInput::Dx(dx) => self.dx = dx,
+            OffsetInput::Dy(dy) => self.dy = dy,
+            OffsetInput::Wrap(wrap) => self.wrap = wrap,
+        }
+    }
+
+    /// Offset typed, `None` while an input is not a number
+    fn offset(&self) -> Option<(i32, i32)> {
+        let parse = |value: &str| match value.trim() {
+            "" => Some(0),
+            value => value.parse().ok(),
+        };
+
+        Some((parse(&self.dx)?, parse(&self.dy)?))
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let apply = Button::new(Text::new("Apply"));
+        let apply = match self.offset() {
+            Some((dx, dy)) => apply.on_press(Message::OffsetLayer(dx, dy, self.wrap)),
+            None => apply,
+        };
+
+        Row::new()
+            .spacing(5)
+            .align_items(Alignment::Center)
+            .push(Text::new("Offset layer by"))
+            .push(
+                text_input("x", &self.dx, |dx| {
+                    Message::OffsetInput(OffsetInput::Dx(dx))
+                })
+                .width(Length::Units(50)),
+            )
+            .push(
+                text_input("y", &self.dy, |dy| {
+                    Message::OffsetInput(OffsetInput::Dy(dy))
+                })
+                .width(Length::Units(50)),
+            )
+            .push(checkbox("Wrap around", self.wrap, |wrap| {
+                Message::OffsetInput(OffsetInput::Wrap(wrap))
+            }))
+            .push(apply)
+            .push(Button::new(Text::new("Cancel")).on_press(Message::OffsetDialogClosed))
+            .into()
+    }
+}
@@ -0,0 +1,116 @@
+use iced::{
+    pure::{
+        checkbox, text_input,
+        widget::{Button, Column, Row, Text},
+        Element,
+    },
+    Alignment, Length,
+};
+
+use crate::{
+    tilemap::{Properties, PropertyValue},
+    Message,
+};
+
+/// Custom properties of the selected tile, with the name of the one being added
+#[derive(Default)]
+pub struct PropertiesPanel {
+    name: String,
+}
+
+impl PropertiesPanel {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    /// View of the properties of `tile`, which are only editable if a tile is selected
+    pub fn view<'a>(
+        &'a self,
+        tile: Option<u32>,
+        properties: Option<&'a Properties>,
+    ) -> Element<'a, Message> {
+        let tile = match tile {
+            Some(tile) => tile,
+            None => return Text::new("Select a tile to edit its properties").into(),
+        };
+
+        let list = properties.into_iter().flatten().fold(
+            Column::new().spacing(2),
+            |column, (name, property)| {
+                let value: Element<'_, Message> = match property {
+                    PropertyValue::Bool(checked) => {
+                        let name = name.clone();
+                        checkbox("", *checked, move |checked| {
+                            Message::TilePropertyChanged(
+                                tile,
+                                name.clone(),
+                                Some(PropertyValue::Bool(checked)),
+                            )
+                        })
+                        .into()
+                    }
+                    PropertyValue::String(text) => {
+                        let name = name.clone();
+                        text_input("value", text, move |text| {
+                            Message::TilePropertyChanged(
+                                tile,
+                                name.clone(),
+                                Some(PropertyValue::String(text)),
+                            )
+                        })
+                        .width(Length::Fill)
+                        .into()
+                    }
+                };
+
+                column.push(
+                    Row::new()
+                        .spacing(5)
+                        .align_items(Alignment::Center)
+                        .push(Text::new(name))
+                        .push(value)
+                        .push(
+                            Button::new(Text::new("x")).on_press(Message::TilePropertyChanged(
+                                tile,
+                                name.clone(),
+                                None,
+                            )),
+                        ),
+                )
+            },
+        );
+
+        // a property is only added once, and needs a name
+        let name = self.name.trim();
+        let can_add = !name.is_empty() && !properties.is_some_and(|p| p.contains_key(name));
+        let add = |label, property| {
+            let button = Button::new(Text::new(label));
+            if can_add {
+                button.on_press(Message::TilePropertyAdded(tile, property))
+            } else {
+                button
+            }
+        };
+
+        Column::new()
+            .spacing(5)
+            .push(Text::new(format!("Tile {}", tile)))
+            .push(list)
+            .push(
+                Row::new()
+                    .spacing(2)
+                    .align_items(Alignment::Center)
+                    .push(
+                        text_input("name", &self.name, Message::PropertyNameChanged)
+                            .width(Length::Fill),
+                    )
+                    .push(add("Flag", PropertyValue::Bool(true)))
+                    .push(add("Text", PropertyValue::String(String::new()))),
+            )
+            .into()
+    }
+}
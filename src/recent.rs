@@ -0,0 +1,71 @@
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const MAX_RECENT_FILES: usize = 10;
+
+/// Maps recently opened or saved, most recent first
+#[derive(Default, Serialize, Deserialize)]
+pub struct RecentFiles {
+    files: Vec<PathBuf>,
+}
+
+/// Entry of the recent files list, displayable in a pick list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecentFile(pub PathBuf);
+
+impl Display for RecentFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.display())
+    }
+}
+
+impl RecentFiles {
+    /// Reads the list from the config directory, an unreadable list is considered empty
+    pub fn load() -> Self {
+        storage_file()
+            .and_then(|file| fs::read(file).ok())
+            .and_then(|content| ron::de::from_bytes(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let file = storage_file()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no configuration directory"))?;
+
+        let content = ron::ser::to_string_pretty(self, PrettyConfig::new())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(file, content)
+    }
+
+    /// Puts `file` at the top of the list
+    pub fn add(&mut self, file: PathBuf) {
+        self.remove(&file);
+        self.files.insert(0, file);
+        self.files.truncate(MAX_RECENT_FILES);
+    }
+
+    pub fn remove(&mut self, file: &Path) {
+        self.files.retain(|f| f != file);
+    }
+
+    pub fn latest(&self) -> Option<&Path> {
+        self.files.first().map(PathBuf::as_path)
+    }
+
+    pub fn entries(&self) -> Vec<RecentFile> {
+        self.files.iter().cloned().map(RecentFile).collect()
+    }
+}
+
+fn storage_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("tilemap_editor").join("recent.ron"))
+}
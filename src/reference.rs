@@ -0,0 +1,193 @@
+//! Image shown under the map as a tracing guide, never saved with it
+
+use std::{fs::File, io, path::Path};
+
+use iced::{
+    canvas::Frame,
+    pure::{
+        checkbox, slider,
+        widget::{Button, Row, Text},
+        Element,
+    },
+    Alignment, Color, Length, Point, Rectangle, Size,
+};
+
+use crate::Message;
+
+/// Largest side of the image kept, larger ones being downsampled
+const MAX_SIDE: u32 = 160;
+
+/// Smallest and largest side of a pixel of the image, in pixels of the tiles
+pub const MIN_SCALE: f32 = 0.05;
+pub const MAX_SCALE: f32 = 256.0;
+
+pub struct Reference {
+    /// Colours of the pixels kept, row after row
+    pixels: Vec<[u8; 4]>,
+    width: u32,
+    height: u32,
+    /// Side of a pixel kept, in pixels of the original image
+    pixel_size: u32,
+    pub visible: bool,
+    pub opacity: f32,
+    /// Position of the top left corner of the image, in pixels of the tiles
+    pub offset: (i32, i32),
+    /// Side of a pixel of the original image, in pixels of the tiles
+    pub scale: f32,
+    /// Whether the image is kept on the tile boundaries, see [`Reference::snap`]
+    pub snap: bool,
+}
+
+impl Reference {
+    /// Reads the PNG image `file`, shown half transparent at the top left corner of the map
+    pub fn open(file: &Path) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(File::open(file)?);
+        // palettes and bit depths are resolved into 8 bits channels
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+
+        let rgba: fn(&[u8]) -> [u8; 4] = match info.color_type {
+            png::ColorType::Grayscale => |p| [p[0], p[0], p[0], 255],
+            png::ColorType::GrayscaleAlpha => |p| [p[0], p[0], p[0], p[1]],
+            png::ColorType::Rgb => |p| [p[0], p[1], p[2], 255],
+            png::ColorType::Rgba => |p| [p[0], p[1], p[2], p[3]],
+            png::ColorType::Indexed => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the palette of the image could not be read",
+                ))
+            }
+        };
+        let samples = info.color_type.samples();
+        let pixel = |x: u32, y: u32| {
+            let start = y as usize * info.line_size + x as usize * samples;
+            rgba(&buffer[start..start + samples])
+        };
+
+        let pixel_size = u32::max(info.width, info.height).div_ceil(MAX_SIDE).max(1);
+        let (width, height) = (
+            info.width.div_ceil(pixel_size),
+            info.height.div_ceil(pixel_size),
+        );
+
+        // close colours are merged, so that flat areas are drawn as few rectangles
+        let quantize = |channel: u8| channel & 0xF0 | channel >> 4;
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let [r, g, b, a] = pixel(x * pixel_size, y * pixel_size);
+                match a {
+                    0 => [0; 4],
+                    _ => [quantize(r), quantize(g), quantize(b), a],
+                }
+            })
+            .collect();
+
+        Ok(Reference {
+            pixels,
+            width,
+            height,
+            pixel_size,
+            visible: true,
+            opacity: 0.5,
+            offset: (0, 0),
+            scale: 1.0,
+            snap: false,
+        })
+    }
+
+    /// Moves the image to the closest tile boundary, and scales it so that its pixels cover a
+    /// whole number of tiles
+    pub fn snap(&mut self, tile_size: u16) {
+        let size = tile_size as f32;
+        let snap = |offset: i32| ((offset as f32 / size).round() * size) as i32;
+
+        self.offset = (snap(self.offset.0), snap(self.offset.1));
+        self.scale = (self.scale / size).round().max(1.0) * size;
+    }
+
+    /// Draws the image in the frame of the map, clipped to `area`, the visible part of the map
+    pub fn draw(&self, frame: &mut Frame, area: Rectangle, tile_side: f32, tile_size: u16) {
+        let tile_pixel = tile_side / tile_size as f32;
+        let side = tile_pixel * self.scale * self.pixel_size as f32;
+        let origin = Point::new(
+            self.offset.0 as f32 * tile_pixel,
+            self.offset.1 as f32 * tile_pixel,
+        );
+
+        for y in 0..self.height {
+            let top = origin.y + y as f32 * side;
+            if top + side <= area.y || top >= area.y + area.height {
+                continue;
+            }
+
+            let row = &self.pixels[(y * self.width) as usize..((y + 1) * self.width) as usize];
+            let mut x = 0;
+            while x < row.len() {
+                // merge consecutive pixels of the same colour into a single rectangle
+                let pixel = row[x];
+                let length = row[x..].iter().take_while(|&&p| p == pixel).count();
+
+                let rect = Rectangle::new(
+                    Point::new(origin.x + x as f32 * side, top),
+                    Size::new(length as f32 * side, side),
+                );
+                if let (Some(rect), true) = (rect.intersection(&area), pixel[3] != 0) {
+                    frame.fill_rectangle(
+                        rect.position(),
+                        rect.size(),
+                        Color::from_rgba8(
+                            pixel[0],
+                            pixel[1],
+                            pixel[2],
+                            pixel[3] as f32 / 255.0 * self.opacity,
+                        ),
+                    );
+                }
+
+                x += length;
+            }
+        }
+    }
+
+    /// View of the settings of the image, over tiles of `tile_size` pixels
+    pub fn view(&self, tile_size: u16) -> Element<'_, Message> {
+        // snapped, the image moves and grows by whole tiles
+        let (step, smaller, larger) = if self.snap {
+            let size = tile_size as f32;
+            (tile_size as i32, self.scale - size, self.scale + size)
+        } else {
+            (1, self.scale / 1.25, self.scale * 1.25)
+        };
+
+        Row::new()
+            .spacing(5)
+            .align_items(Alignment::Center)
+            .push(checkbox("Reference", self.visible, Message::ReferenceShown))
+            .push(
+                slider(0.0..=1.0, self.opacity, Message::SetReferenceOpacity)
+                    .step(0.05)
+                    .width(Length::Units(100)),
+            )
+            .push(checkbox(
+                "Snap to tiles",
+                self.snap,
+                Message::ReferenceSnapped,
+            ))
+            .push(Text::new(format!("Scale: {:.2}", self.scale)))
+            .push(Button::new(Text::new("-")).on_press(Message::SetReferenceScale(smaller)))
+            .push(Button::new(Text::new("+")).on_press(Message::SetReferenceScale(larger)))
+            .push(Text::new(format!(
+                "Position: ({}, {})",
+                self.offset.0, self.offset.1
+            )))
+            .push(Button::new(Text::new("<")).on_press(Message::MoveReference(-step, 0)))
+            .push(Button::new(Text::new(">")).on_press(Message::MoveReference(step, 0)))
+            .push(Button::new(Text::new("^")).on_press(Message::MoveReference(0, -step)))
+            .push(Button::new(Text::new("v")).on_press(Message::MoveReference(0, step)))
+            .push(Button::new(Text::new("Remove")).on_press(Message::ReferenceClosed))
+            .into()
+    }
+}
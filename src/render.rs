@@ -0,0 +1,121 @@
+//! Image of a whole map, drawn with its tilesheets
+
+use std::{fs::File, io, io::BufWriter, path::Path};
+
+use asefile::AsepriteFile;
+
+use crate::{tileimage::TileImages, tilemap::TileMap};
+
+/// Writes `map` as a PNG image, each tile being `tile_size` pixels wide.
+///
+/// The layers are drawn over one another, and the tiles with their transform and tint.
+/// Animated tiles are drawn as the first frame of their animation, and the tiles of which the
+/// sheet or the frame is missing are left out.
+pub fn write_png(
+    map: &TileMap,
+    sheets: &[AsepriteFile],
+    tile_size: u16,
+    file: &Path,
+) -> io::Result<()> {
+    let (width, height) = map.get_dimensions();
+    let (width, height) = (
+        width as u32 * tile_size as u32,
+        height as u32 * tile_size as u32,
+    );
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(file)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()?
+        .write_image_data(&render(map, sheets, tile_size))?;
+    Ok(())
+}
+
+/// Rows of the RGBA pixels of `map`, see [`write_png`]
+fn render(map: &TileMap, sheets: &[AsepriteFile], tile_size: u16) -> Vec<u8> {
+    let (width, height) = map.get_dimensions();
+    let size = tile_size as usize;
+    let row_width = width as usize * size;
+    let mut pixels = vec![0; row_width * height as usize * size * 4];
+
+    let images = TileImages::default();
+    for layer in 0..map.layer_count() {
+        for y in 0..height {
+            for x in 0..width {
+                let tile = match map.get_layer_tile(x, y, layer) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                let sheet = match sheets.get(tile.sheet as usize) {
+                    Some(sheet) if tile.value < sheet.num_frames() => sheet,
+                    _ => continue,
+                };
+
+                images.get(sheet, tile, tile_size).paint(
+                    &mut pixels,
+                    row_width,
+                    (x as usize * size, y as usize * size),
+                );
+            }
+        }
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::Tile;
+
+    #[test]
+    fn tints_are_baked_into_the_image() {
+        let sheet = AsepriteFile::read_file(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/tile_sheet.aseprite"),
+        )
+        .unwrap();
+        let tile_size = sheet.width() as u16;
+
+        let tile = Tile::new(1, false, false);
+        let mut map = TileMap::with_layers(3, 1, ["Ground".to_string()]);
+        map.set_tile(0, 0, Some(tile), 0);
+        map.set_tile(
+            1,
+            0,
+            Some(Tile {
+                tint: Some([255, 0, 0]),
+                ..tile
+            }),
+            0,
+        );
+        let pixels = render(&map, &[sheet], tile_size);
+
+        let size = tile_size as usize;
+        let pixel = |x: usize, y: usize| {
+            let start = (y * size * 3 + x) * 4;
+            [
+                pixels[start],
+                pixels[start + 1],
+                pixels[start + 2],
+                pixels[start + 3],
+            ]
+        };
+        let mut drawn = 0;
+        for y in 0..size {
+            for x in 0..size {
+                let [r, _, _, a] = pixel(x, y);
+                if a != 0 {
+                    drawn += 1;
+                    assert_eq!(pixel(x + size, y), [r, 0, 0, a], "({x}, {y})");
+                }
+                assert_eq!(
+                    pixel(x + 2 * size, y),
+                    [0; 4],
+                    "the empty cell at ({x}, {y})"
+                );
+            }
+        }
+        assert!(drawn > 0, "the tile has pixels");
+    }
+}
@@ -0,0 +1,98 @@
+use iced::{
+    pure::{
+        checkbox, pick_list, text_input,
+        widget::{Button, Row, Text},
+        Element,
+    },
+    Alignment, Length,
+};
+
+use crate::{tilemap::Layer, Message, SheetEntry};
+
+/// Inputs of the tile replacement, shown below the toolbar while open
+#[derive(Default)]
+pub struct ReplaceDialog {
+    from_sheet: u8,
+    from: String,
+    to_sheet: u8,
+    to: String,
+    whole_map: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum ReplaceInput {
+    FromSheet(u8),
+    From(String),
+    ToSheet(u8),
+    To(String),
+    WholeMap(bool),
+}
+
+impl ReplaceDialog {
+    /// Dialog replacing `from` of `sheet`, usually the selected tile, by a tile of the same sheet
+    pub fn new(sheet: u8, from: Option<u32>) -> Self {
+        ReplaceDialog {
+            from_sheet: sheet,
+            from: from.map(|value| value.to_string()).unwrap_or_default(),
+            to_sheet: sheet,
+            ..Default::default()
+        }
+    }
+
+    pub fn update(&mut self, input: ReplaceInput) {
+        match input {
+            ReplaceInput::FromSheet(sheet) => self.from_sheet = sheet,
+            ReplaceInput::From(from) => self.from = from,
+            ReplaceInput::ToSheet(sheet) => self.to_sheet = sheet,
+            ReplaceInput::To(to) => self.to = to,
+            ReplaceInput::WholeMap(whole_map) => self.whole_map = whole_map,
+        }
+    }
+
+    /// View of the dialog, replacing in `layer` unless the whole map is chosen, the tiles being
+    /// picked among `sheets`
+    pub fn view(&self, layer: Layer, sheets: Vec<SheetEntry>) -> Element<'_, Message> {
+        let apply = Button::new(Text::new("Apply"));
+        let apply = match (self.from.trim().parse(), self.to.trim().parse()) {
+            (Ok(from), Ok(to)) => apply.on_press(Message::ReplaceTile(
+                (self.from_sheet, from),
+                (self.to_sheet, to),
+                (!self.whole_map).then_some(layer),
+            )),
+            _ => apply,
+        };
+
+        let sheet_list = |selected: u8, input: fn(u8) -> ReplaceInput| {
+            let current = sheets.iter().find(|entry| entry.sheet == selected).cloned();
+            pick_list(sheets.clone(), current, move |entry: SheetEntry| {
+                Message::ReplaceInput(input(entry.sheet))
+            })
+        };
+
+        Row::new()
+            .spacing(5)
+            .align_items(Alignment::Center)
+            .push(Text::new("Replace tile"))
+            .push(sheet_list(self.from_sheet, ReplaceInput::FromSheet))
+            .push(
+                text_input("from", &self.from, |from| {
+                    Message::ReplaceInput(ReplaceInput::From(from))
+                })
+                .width(Length::Units(50)),
+            )
+            .push(Text::new("with"))
+            .push(sheet_list(self.to_sheet, ReplaceInput::ToSheet))
+            .push(
+                text_input("to", &self.to, |to| {
+                    Message::ReplaceInput(ReplaceInput::To(to))
+                })
+                .width(Length::Units(50)),
+            )
+            .push(checkbox("Whole map", self.whole_map, |whole_map| {
+                Message::ReplaceInput(ReplaceInput::WholeMap(whole_map))
+            }))
+            .push(apply)
+            .push(Button::new(Text::new("Cancel")).on_press(Message::ReplaceDialogClosed))
+            .into()
+    }
+}
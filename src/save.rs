@@ -1,45 +1,366 @@
 use ron::ser::PrettyConfig;
-use serde::{Deserialize, Serialize};
-use std::{fs, io, path::PathBuf};
+use serde::{de::IgnoredAny, Deserialize, Deserializer, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io, iter,
+    path::{Path, PathBuf},
+};
 
-use crate::tilemap::{Layer as TMLayer, Tile, TileMap};
+use crate::{
+    tiled,
+    tilemap::{MapMetadata, Properties, Tile, TileMap},
+};
+
+/// Version of the format maps are saved in.
+///
+/// Maps saved before there was a version are of version 0 when they have a background and a
+/// foreground, see [`TwoLayersStorage`], and 1 when they have a list of layers, see
+/// [`UncompressedStorage`]. They are upgraded when loaded.
+///
+/// Version 3 added the metadata, 4 the properties of the tiles and 5 their tint. The text
+/// formats default the fields they are missing, the binary one reads the older layouts, see
+/// [`BinaryStorage`].
+const FORMAT_VERSION: u32 = 5;
 
 #[derive(Serialize, Deserialize)]
 struct TileMapStorage {
+    /// Always [`FORMAT_VERSION`] when saved, a more recent version cannot be read
+    #[serde(default)]
+    version: u32,
     width: u16,
     height: u16,
-    background: Layer,
-    foreground: Layer,
+    layers: Vec<Layer>,
+    #[serde(default, skip_serializing_if = "MapMetadata::is_empty")]
+    metadata: MapMetadata,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    tile_properties: BTreeMap<u32, Properties>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct Layer {
+    #[serde(default)]
+    name: String,
+    /// Rows of tiles one after the other, runs of the same tile being stored only once with
+    /// their length
+    tiles: Vec<(u32, Option<Tile>)>,
+}
+
+/// Fields telling which version of the format a map was saved in, whatever its layout
+#[derive(Deserialize)]
+struct Header {
+    /// Missing from the maps saved before there was a version
+    #[serde(default)]
+    version: u32,
+    #[serde(default, deserialize_with = "present")]
+    layers: bool,
+}
+
+impl Header {
+    fn version(&self) -> u32 {
+        match self.version {
+            // the maps without a version are told apart by their layers
+            0 if self.layers => 1,
+            version => version,
+        }
+    }
+}
+
+/// Whether the field is there, its value being skipped
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    IgnoredAny::deserialize(deserializer).map(|_| true)
+}
+
+/// Format of the maps saved before the runs of tiles were compressed, version 1
+#[derive(Deserialize)]
+struct UncompressedStorage {
+    width: u16,
+    height: u16,
+    layers: Vec<UncompressedLayer>,
+}
+
+/// Format of the maps saved when there were only a background and a foreground, version 0
+#[derive(Deserialize)]
+struct TwoLayersStorage {
+    width: u16,
+    height: u16,
+    background: UncompressedLayer,
+    foreground: UncompressedLayer,
+}
+
+#[derive(Deserialize)]
+struct UncompressedLayer {
+    #[serde(default)]
+    name: String,
+    /// Rows of tiles one after the other, like [`Layer::tiles`]
     tiles: Vec<Option<Tile>>,
 }
 
+/// Same as [`TileMapStorage`] in the binary format, which needs every field of the tiles.
+///
+/// Fields are not named in it, so the ones added later cannot be defaulted: the layouts saved
+/// before them are read by the storages below, picked by the version the map starts with.
+#[derive(Serialize, Deserialize)]
+struct BinaryStorage {
+    version: u32,
+    width: u16,
+    height: u16,
+    layers: Vec<BinaryLayer<BinaryTile>>,
+    metadata: MapMetadata,
+    tile_properties: BTreeMap<u32, Properties>,
+}
+
+/// Binary format of the maps saved before tiles had a tint, version 4
+#[derive(Deserialize)]
+struct NoTintBinaryStorage {
+    version: u32,
+    width: u16,
+    height: u16,
+    layers: Vec<BinaryLayer<NoTintBinaryTile>>,
+    metadata: MapMetadata,
+    tile_properties: BTreeMap<u32, Properties>,
+}
+
+/// Binary format of the maps saved before tiles had properties, version 3
+#[derive(Deserialize)]
+struct NoPropertiesBinaryStorage {
+    version: u32,
+    width: u16,
+    height: u16,
+    layers: Vec<BinaryLayer<NoTintBinaryTile>>,
+    metadata: MapMetadata,
+}
+
+/// Binary format of the maps saved before they had metadata, version 2
+#[derive(Deserialize)]
+struct NoMetadataBinaryStorage {
+    version: u32,
+    width: u16,
+    height: u16,
+    layers: Vec<BinaryLayer<NoTintBinaryTile>>,
+}
+
+impl From<NoTintBinaryStorage> for BinaryStorage {
+    fn from(map: NoTintBinaryStorage) -> Self {
+        BinaryStorage {
+            version: map.version,
+            width: map.width,
+            height: map.height,
+            layers: map
+                .layers
+                .into_iter()
+                .map(|layer| BinaryLayer {
+                    name: layer.name,
+                    tiles: layer
+                        .tiles
+                        .into_iter()
+                        .map(|(count, tile)| (count, tile.map(Into::into)))
+                        .collect(),
+                })
+                .collect(),
+            metadata: map.metadata,
+            tile_properties: map.tile_properties,
+        }
+    }
+}
+
+impl From<NoPropertiesBinaryStorage> for NoTintBinaryStorage {
+    fn from(map: NoPropertiesBinaryStorage) -> Self {
+        NoTintBinaryStorage {
+            version: map.version,
+            width: map.width,
+            height: map.height,
+            layers: map.layers,
+            metadata: map.metadata,
+            tile_properties: BTreeMap::new(),
+        }
+    }
+}
+
+impl From<NoMetadataBinaryStorage> for NoPropertiesBinaryStorage {
+    fn from(map: NoMetadataBinaryStorage) -> Self {
+        NoPropertiesBinaryStorage {
+            version: map.version,
+            width: map.width,
+            height: map.height,
+            layers: map.layers,
+            metadata: Default::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinaryLayer<T> {
+    name: String,
+    tiles: Vec<(u32, Option<T>)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinaryTile {
+    sheet: u8,
+    value: u32,
+    h_flip: bool,
+    v_flip: bool,
+    rotation: u8,
+    anim: Option<u32>,
+    tint: Option<[u8; 3]>,
+}
+
+/// Tile of the binary layouts saved before tiles had a tint
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+struct NoTintBinaryTile {
+    sheet: u8,
+    value: u32,
+    h_flip: bool,
+    v_flip: bool,
+    rotation: u8,
+    anim: Option<u32>,
+}
+
+impl From<NoTintBinaryTile> for BinaryTile {
+    fn from(tile: NoTintBinaryTile) -> Self {
+        BinaryTile {
+            sheet: tile.sheet,
+            value: tile.value,
+            h_flip: tile.h_flip,
+            v_flip: tile.v_flip,
+            rotation: tile.rotation,
+            anim: tile.anim,
+            tint: None,
+        }
+    }
+}
+
+impl From<TileMapStorage> for BinaryStorage {
+    fn from(map: TileMapStorage) -> Self {
+        BinaryStorage {
+            version: map.version,
+            width: map.width,
+            height: map.height,
+            layers: map
+                .layers
+                .into_iter()
+                .map(|layer| BinaryLayer {
+                    name: layer.name,
+                    tiles: layer
+                        .tiles
+                        .into_iter()
+                        .map(|(count, tile)| {
+                            let tile = tile.map(|tile| BinaryTile {
+                                sheet: tile.sheet,
+                                value: tile.value,
+                                h_flip: tile.h_flip,
+                                v_flip: tile.v_flip,
+                                rotation: tile.rotation,
+                                anim: tile.anim,
+                                tint: tile.tint,
+                            });
+                            (count, tile)
+                        })
+                        .collect(),
+                })
+                .collect(),
+            metadata: map.metadata,
+            tile_properties: map.tile_properties,
+        }
+    }
+}
+
+impl From<BinaryStorage> for TileMapStorage {
+    fn from(map: BinaryStorage) -> Self {
+        TileMapStorage {
+            version: map.version,
+            width: map.width,
+            height: map.height,
+            layers: map
+                .layers
+                .into_iter()
+                .map(|layer| Layer {
+                    name: layer.name,
+                    tiles: layer
+                        .tiles
+                        .into_iter()
+                        .map(|(count, tile)| {
+                            let tile = tile.map(|tile| Tile {
+                                sheet: tile.sheet,
+                                rotation: tile.rotation,
+                                anim: tile.anim,
+                                tint: tile.tint,
+                                ..Tile::new(tile.value, tile.h_flip, tile.v_flip)
+                            });
+                            (count, tile)
+                        })
+                        .collect(),
+                })
+                .collect(),
+            metadata: map.metadata,
+            tile_properties: map.tile_properties,
+        }
+    }
+}
+
+impl From<UncompressedStorage> for TileMapStorage {
+    fn from(map: UncompressedStorage) -> Self {
+        TileMapStorage {
+            version: FORMAT_VERSION,
+            width: map.width,
+            height: map.height,
+            layers: map
+                .layers
+                .into_iter()
+                .map(|layer| Layer {
+                    name: layer.name,
+                    tiles: pack(layer.tiles),
+                })
+                .collect(),
+            metadata: Default::default(),
+            tile_properties: BTreeMap::new(),
+        }
+    }
+}
+
+impl From<TwoLayersStorage> for UncompressedStorage {
+    fn from(map: TwoLayersStorage) -> Self {
+        UncompressedStorage {
+            width: map.width,
+            height: map.height,
+            layers: vec![
+                UncompressedLayer {
+                    name: "Background".to_string(),
+                    ..map.background
+                },
+                UncompressedLayer {
+                    name: "Foreground".to_string(),
+                    ..map.foreground
+                },
+            ],
+        }
+    }
+}
+
 impl From<TileMap> for TileMapStorage {
     fn from(map: TileMap) -> Self {
         let (width, height) = map.get_dimensions();
-        let mut out = TileMapStorage {
+
+        TileMapStorage {
+            version: FORMAT_VERSION,
             width,
             height,
-            background: Layer {
-                tiles: Vec::with_capacity((width * height) as usize),
-            },
-            foreground: Layer {
-                tiles: Vec::with_capacity((width * height) as usize),
-            },
-        };
-
-        for y in 0..height {
-            for x in 0..width {
-                let (bg_tile, fg_tile) = map.get_tile(x, y);
-                out.background.tiles.push(bg_tile);
-                out.foreground.tiles.push(fg_tile);
-            }
+            layers: map
+                .layers()
+                .into_iter()
+                .map(|entry| Layer {
+                    name: entry.name,
+                    tiles: pack(
+                        (0..height)
+                            .flat_map(|y| (0..width).map(move |x| (x, y)))
+                            .map(|(x, y)| map.get_layer_tile(x, y, entry.layer)),
+                    ),
+                })
+                .collect(),
+            metadata: map.metadata().clone(),
+            tile_properties: map.tile_properties().clone(),
         }
-
-        out
     }
 }
 
@@ -47,52 +368,561 @@ impl From<TileMapStorage> for TileMap {
     fn from(map: TileMapStorage) -> Self {
         let (width, height) = (map.width, map.height);
 
-        let mut out_map = TileMap::new(width, height);
-
-        for y in 0..height {
-            for x in 0..width {
-                out_map.set_tile(
-                    x,
-                    y,
-                    map.background.tiles[(x + y * width) as usize],
-                    TMLayer::Background,
-                );
+        let mut out_map = TileMap::with_layers(
+            width,
+            height,
+            map.layers.iter().map(|layer| layer.name.clone()),
+        );
 
-                out_map.set_tile(
-                    x,
-                    y,
-                    map.foreground.tiles[(x + y * width) as usize],
-                    TMLayer::Foreground,
-                );
+        for (id, layer) in map.layers.iter().enumerate() {
+            let tiles = unpack(&layer.tiles, width as usize * height as usize);
+            for y in 0..height {
+                for x in 0..width {
+                    let index = x as usize + y as usize * width as usize;
+                    out_map.set_tile(x, y, tiles.get(index).copied().flatten(), id);
+                }
             }
         }
+        out_map.set_metadata(map.metadata);
+        out_map.set_tile_properties(map.tile_properties);
 
         out_map
     }
 }
 
+/// Merges the runs of the same tile
+fn pack<T: PartialEq>(tiles: impl IntoIterator<Item = T>) -> Vec<(u32, T)> {
+    let mut runs: Vec<(u32, T)> = Vec::new();
+
+    for tile in tiles {
+        match runs.last_mut() {
+            Some((count, last)) if *last == tile => *count += 1,
+            _ => runs.push((1, tile)),
+        }
+    }
+
+    runs
+}
+
+/// Expands the runs, up to `len` tiles
+fn unpack<T: Clone>(runs: &[(u32, T)], len: usize) -> Vec<T> {
+    runs.iter()
+        .flat_map(|(count, tile)| iter::repeat_n(tile.clone(), *count as usize))
+        .take(len)
+        .collect()
+}
+
+/// Reads a map saved in any version of the format, upgrading it to the current one.
+///
+/// The layout is read in the version given by `header`, which fails for maps saved by a newer
+/// version of the editor.
+fn migrate<E: Into<Box<dyn std::error::Error + Send + Sync>>>(
+    header: Result<Header, E>,
+    current: impl FnOnce() -> Result<TileMapStorage, E>,
+    uncompressed: impl FnOnce() -> Result<UncompressedStorage, E>,
+    two_layers: impl FnOnce() -> Result<TwoLayersStorage, E>,
+) -> io::Result<TileMapStorage> {
+    let invalid = |err: E| io::Error::new(io::ErrorKind::InvalidData, err);
+
+    let map = match header.map_err(invalid)?.version() {
+        0 => UncompressedStorage::from(two_layers().map_err(invalid)?).into(),
+        1 => uncompressed().map_err(invalid)?.into(),
+        version if version <= FORMAT_VERSION => current().map_err(invalid)?,
+        version => return Err(too_recent(version)),
+    };
+    validate(&map)?;
+    Ok(map)
+}
+
+fn too_recent(version: u32) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "the map was saved in version {} of the format, only versions up to {} are supported",
+            version, FORMAT_VERSION
+        ),
+    )
+}
+
+/// Fails for maps of which a layer does not have exactly one tile per cell
+fn validate(map: &TileMapStorage) -> io::Result<()> {
+    let cells = map.width as u64 * map.height as u64;
+    for layer in &map.layers {
+        let tiles: u64 = layer.tiles.iter().map(|&(count, _)| count as u64).sum();
+        if tiles != cells {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the layer {:?} has {} tiles, instead of {} for a {}x{} map",
+                    layer.name, tiles, cells, map.width, map.height
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Saves the map in JSON if the extension is `json`, in the compact binary format if it is
+/// `tmb`, in RON otherwise
 pub fn save_in_file(map: TileMap, file: PathBuf) -> io::Result<()> {
+    if has_extension(&file, "json") {
+        save_json(map, file)
+    } else if has_extension(&file, "tmb") {
+        save_bin(map, file)
+    } else {
+        save_ron(map, file)
+    }
+}
+
+fn save_ron(map: TileMap, file: PathBuf) -> io::Result<()> {
     let storage: TileMapStorage = map.into();
 
-    // unwrap because the struct cannot be ill-formed, and I don’t care enough
-    fs::write(
-        file,
-        ron::ser::to_string_pretty(
-            &storage,
-            PrettyConfig::new()
-                .depth_limit(2)
-                .compact_arrays(true)
-                .new_line(String::from("\n")),
-        )
-        .unwrap(),
-    )?;
+    let content = ron::ser::to_string_pretty(
+        &storage,
+        PrettyConfig::new()
+            .depth_limit(2)
+            .compact_arrays(true)
+            .new_line(String::from("\n")),
+    )
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
 
-    Ok(())
+    fs::write(file, content)
 }
 
+fn save_json(map: TileMap, file: PathBuf) -> io::Result<()> {
+    let storage: TileMapStorage = map.into();
+
+    fs::write(file, serde_json::to_string_pretty(&storage)?)
+}
+
+fn save_bin(map: TileMap, file: PathBuf) -> io::Result<()> {
+    let storage: BinaryStorage = TileMapStorage::from(map).into();
+
+    let content = bincode::serialize(&storage)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    fs::write(file, content)
+}
+
+/// Loads a map saved by the editor, or a Tiled map if the extension is `tmx`
 pub fn load_from_file(file: &PathBuf) -> io::Result<TileMap> {
+    if has_extension(file, "tmx") {
+        let xml = String::from_utf8(fs::read(file)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        tiled::from_tmx(&xml)
+    } else if has_extension(file, "json") {
+        load_json(file)
+    } else if has_extension(file, "tmb") {
+        load_bin(file)
+    } else {
+        load_ron(file)
+    }
+}
+
+fn load_ron(file: &PathBuf) -> io::Result<TileMap> {
     let content = fs::read(file)?;
 
-    let map: TileMapStorage = ron::de::from_bytes(&content).unwrap();
+    let map = migrate(
+        ron::de::from_bytes(&content),
+        || ron::de::from_bytes(&content),
+        || ron::de::from_bytes(&content),
+        || ron::de::from_bytes(&content),
+    )?;
+    Ok(map.into())
+}
+
+fn load_json(file: &PathBuf) -> io::Result<TileMap> {
+    let content = fs::read(file)?;
+
+    let map = migrate(
+        serde_json::from_slice(&content),
+        || serde_json::from_slice(&content),
+        || serde_json::from_slice(&content),
+        || serde_json::from_slice(&content),
+    )?;
     Ok(map.into())
 }
+
+fn load_bin(file: &PathBuf) -> io::Result<TileMap> {
+    let content = fs::read(file)?;
+    let invalid = |err| io::Error::new(io::ErrorKind::InvalidData, err);
+
+    // every layout starts with its version
+    let map: TileMapStorage = match bincode::deserialize::<u32>(&content).map_err(invalid)? {
+        2 => bincode::deserialize::<NoMetadataBinaryStorage>(&content)
+            .map(|map| NoTintBinaryStorage::from(NoPropertiesBinaryStorage::from(map)).into()),
+        3 => bincode::deserialize::<NoPropertiesBinaryStorage>(&content)
+            .map(|map| NoTintBinaryStorage::from(map).into()),
+        4 => bincode::deserialize::<NoTintBinaryStorage>(&content).map(Into::into),
+        5 => bincode::deserialize::<BinaryStorage>(&content),
+        version if version > FORMAT_VERSION => return Err(too_recent(version)),
+        version => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("binary maps start at version 2, not {}", version),
+            ))
+        }
+    }
+    .map_err(invalid)?
+    .into();
+    validate(&map)?;
+    Ok(map.into())
+}
+
+/// Whether `file` is loaded from another editor's format, and cannot be saved back
+pub fn is_imported(file: &Path) -> bool {
+    has_extension(file, "tmx")
+}
+
+pub fn has_extension(file: &Path, extension: &str) -> bool {
+    file.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::PropertyValue;
+
+    /// File in the temporary directory, `name` being unique to the test using it
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("tilemap_editor_{}_{}", std::process::id(), name))
+    }
+
+    /// Map with three layers, a different tile in every cell but a few empty ones, some of them
+    /// animated or tinted, and some metadata and properties
+    fn sample_map(width: u16, height: u16) -> TileMap {
+        let mut map = TileMap::with_layers(
+            width,
+            height,
+            ["Ground", "Walls", "Decorations"].map(String::from),
+        );
+        for layer in 0..map.layer_count() {
+            for y in 0..height {
+                for x in 0..width {
+                    let value = (layer as u32 * height as u32 + y as u32) * width as u32 + x as u32;
+                    let tile = (value % 7 != 3).then(|| Tile {
+                        sheet: (value % 2) as u8,
+                        rotation: (value % 4) as u8,
+                        anim: value.is_multiple_of(11).then_some(value % 3),
+                        tint: value.is_multiple_of(13).then_some([255, value as u8, 0]),
+                        ..Tile::new(value, value.is_multiple_of(3), value.is_multiple_of(5))
+                    });
+                    map.set_tile(x, y, tile, layer);
+                }
+            }
+        }
+
+        map.set_metadata(MapMetadata {
+            name: "Sample".to_string(),
+            author: "Tests".to_string(),
+            description: "Every cell differs".to_string(),
+        });
+        map.set_tile_property(1, "solid".to_string(), Some(PropertyValue::Bool(true)));
+        map
+    }
+
+    /// Saves `map` in a file of extension `extension` and loads it back
+    fn round_trip(map: &TileMap, name: &str, extension: &str) -> io::Result<TileMap> {
+        let file = temp_file(&format!("{}.{}", name, extension));
+        save_in_file(map.clone(), file.clone())?;
+        let loaded = load_from_file(&file);
+        fs::remove_file(file)?;
+        loaded
+    }
+
+    #[test]
+    fn saving_in_ron_succeeds() {
+        let map = sample_map(12, 9);
+
+        assert_eq!(round_trip(&map, "saving_in_ron", "ron").unwrap(), map);
+    }
+
+    #[test]
+    fn saving_in_tmb_succeeds() {
+        let map = sample_map(12, 9);
+
+        assert_eq!(round_trip(&map, "saving_in_tmb", "tmb").unwrap(), map);
+    }
+
+    #[test]
+    fn tmb_is_smaller_than_ron() {
+        let map = sample_map(12, 9);
+        let (ron, tmb) = (temp_file("smaller.ron"), temp_file("smaller.tmb"));
+        save_in_file(map.clone(), ron.clone()).unwrap();
+        save_in_file(map, tmb.clone()).unwrap();
+
+        let (ron_size, tmb_size) = (
+            fs::metadata(&ron).unwrap().len(),
+            fs::metadata(&tmb).unwrap().len(),
+        );
+        fs::remove_file(ron).unwrap();
+        fs::remove_file(tmb).unwrap();
+        assert!(
+            tmb_size < ron_size,
+            "{tmb_size} bytes in tmb, {ron_size} in RON"
+        );
+    }
+
+    #[test]
+    fn sparse_maps_are_compressed() {
+        let mut map = TileMap::new(128, 128);
+        for i in 0..16 {
+            map.set_tile(i * 8, i * 8, Some(Tile::new(i as u32, false, false)), 0);
+        }
+        let file = temp_file("sparse.ron");
+        save_in_file(map.clone(), file.clone()).unwrap();
+        let size = fs::metadata(&file).unwrap().len();
+        let loaded = load_from_file(&file).unwrap();
+        fs::remove_file(file).unwrap();
+
+        // written out cell by cell, each empty one would at least take the 5 bytes of `None,`
+        assert!(size < 128 * 128 / 10, "{size} bytes saved");
+        assert_eq!(loaded, map);
+    }
+
+    /// Map saved in `tests/` by a past version of the editor
+    fn fixture(name: &str) -> io::Result<TileMap> {
+        load_from_file(
+            &Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests")
+                .join(name),
+        )
+    }
+
+    /// Map of the fixtures, with their layers named `names`
+    fn fixture_map(names: [&str; 2], transformed: bool) -> TileMap {
+        let mut map = TileMap::with_layers(3, 2, names.map(String::from));
+        map.set_tile(0, 0, Some(Tile::new(1, false, false)), 0);
+        map.set_tile(2, 1, Some(Tile::new(2, true, false)), 0);
+        let tile = Tile::new(3, false, true);
+        let tile = if transformed {
+            Tile {
+                sheet: 1,
+                rotation: 2,
+                ..tile
+            }
+        } else {
+            tile
+        };
+        map.set_tile(1, 0, Some(tile), 1);
+        map
+    }
+
+    #[test]
+    fn maps_of_every_version_are_loaded() {
+        let two_layers = fixture_map(["Background", "Foreground"], false);
+        let layers = fixture_map(["Ground", "Decorations"], true);
+
+        assert_eq!(fixture("version_0.ron").unwrap(), two_layers);
+        assert_eq!(fixture("version_1.ron").unwrap(), layers);
+        assert_eq!(fixture("version_1.json").unwrap(), layers);
+        assert_eq!(fixture("version_2.ron").unwrap(), layers);
+
+        let map = fixture("test.ron").unwrap();
+        assert_eq!(map.get_dimensions(), (32, 32));
+        assert_eq!(map.layer_name(1), "Foreground");
+    }
+
+    #[test]
+    fn maps_of_a_newer_version_are_refused() {
+        let file = temp_file("newer.ron");
+        let newer =
+            fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/version_2.ron"))
+                .unwrap()
+                .replace("version: 2", &format!("version: {}", FORMAT_VERSION + 1));
+        fs::write(&file, newer).unwrap();
+
+        let err = load_from_file(&file).unwrap_err();
+        fs::remove_file(file).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("only versions up to"), "{err}");
+    }
+
+    #[test]
+    fn damaged_maps_report_the_error_of_their_version() {
+        let file = temp_file("damaged.ron");
+        fs::write(
+            &file,
+            "(version: 2, width: 3, height: 2, layers: [(name: 4)])",
+        )
+        .unwrap();
+
+        let err = load_from_file(&file).unwrap_err();
+        fs::remove_file(file).unwrap();
+        // read as a current map, not as an older layout missing its background
+        assert!(!err.to_string().contains("background"), "{err}");
+    }
+
+    #[test]
+    fn layers_missing_tiles_are_refused() {
+        let file = temp_file("short.ron");
+        fs::write(
+            &file,
+            "(width: 3, height: 2, layers: [(name: \"Ground\", tiles: [None, None, None, None])])",
+        )
+        .unwrap();
+
+        let err = load_from_file(&file).unwrap_err();
+        fs::remove_file(file).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(
+            err.to_string().contains("has 4 tiles, instead of 6"),
+            "{err}"
+        );
+    }
+
+    /// Loads the map saved in `content` in the binary format
+    fn load_bytes(content: Vec<u8>, name: &str) -> io::Result<TileMap> {
+        let file = temp_file(&format!("{}.tmb", name));
+        fs::write(&file, content)?;
+        let loaded = load_from_file(&file);
+        fs::remove_file(file)?;
+        loaded
+    }
+
+    /// Layer of a 2x1 map saved before tiles had a tint, with a tile on its right
+    fn binary_layer() -> (String, Vec<(u32, Option<NoTintBinaryTile>)>) {
+        let tile = NoTintBinaryTile {
+            sheet: 1,
+            value: 4,
+            h_flip: true,
+            v_flip: false,
+            rotation: 3,
+            anim: None,
+        };
+        ("Ground".to_string(), vec![(1, None), (1, Some(tile))])
+    }
+
+    #[test]
+    fn binary_maps_without_metadata_are_loaded() {
+        let content = bincode::serialize(&(2u32, 2u16, 1u16, vec![binary_layer()])).unwrap();
+        let map = load_bytes(content, "no_metadata").unwrap();
+
+        let mut expected = TileMap::with_layers(2, 1, ["Ground".to_string()]);
+        let tile = Tile {
+            sheet: 1,
+            rotation: 3,
+            ..Tile::new(4, true, false)
+        };
+        expected.set_tile(1, 0, Some(tile), 0);
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn binary_maps_of_an_unknown_version_are_refused() {
+        for version in [0, 1, FORMAT_VERSION + 1] {
+            let content = bincode::serialize(&(version, 2u16, 1u16, vec![binary_layer()])).unwrap();
+
+            let err = load_bytes(content, "unknown_version").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "version {version}");
+        }
+    }
+
+    #[test]
+    fn binary_maps_without_properties_are_loaded() {
+        let metadata = MapMetadata {
+            name: "Old".to_string(),
+            ..Default::default()
+        };
+        let content =
+            bincode::serialize(&(3u32, 2u16, 1u16, vec![binary_layer()], metadata.clone()))
+                .unwrap();
+        let map = load_bytes(content, "no_properties").unwrap();
+
+        assert_eq!(*map.metadata(), metadata);
+        assert!(map.tile_properties().is_empty());
+        assert_eq!(map.get_layer_tile(1, 0, 0).map(|tile| tile.value), Some(4));
+    }
+
+    #[test]
+    fn non_square_maps_keep_every_cell() {
+        let map = sample_map(7, 3);
+
+        let storage = TileMapStorage::from(map.clone());
+        assert_eq!((storage.width, storage.height), (7, 3));
+        assert_eq!(TileMap::from(storage), map);
+
+        for extension in ["ron", "json", "tmb"] {
+            let loaded = round_trip(&map, "non_square", extension).unwrap();
+            assert_eq!(loaded.get_dimensions(), (7, 3), "{extension}");
+            assert_eq!(loaded, map, "{extension}");
+        }
+    }
+
+    #[test]
+    fn the_non_square_test_map_is_not_transposed() {
+        let map = fixture("non_square.ron").unwrap();
+        assert_eq!(map.get_dimensions(), (7, 3));
+
+        for layer in 0..2 {
+            for y in 0..3 {
+                for x in 0..7 {
+                    let value = map.get_layer_tile(x, y, layer).map(|tile| tile.value);
+                    let expected = 100 * layer as u32 + 10 * y as u32 + x as u32;
+                    assert!(
+                        value.is_none_or(|value| value == expected),
+                        "({x}, {y}) of {layer}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn corners_stay_in_place_in_every_format() {
+        let (width, height) = (4, 2);
+        let tile = |x: u16, y: u16| Tile::new((y * width + x) as u32, false, false);
+        let mut map = TileMap::with_layers(width, height, ["Ground".to_string()]);
+        for y in 0..height {
+            for x in 0..width {
+                map.set_tile(x, y, Some(tile(x, y)), 0);
+            }
+        }
+
+        let tmx = tiled::to_tmx(
+            &map,
+            8,
+            &[tiled::Tileset {
+                name: "Sheet".to_string(),
+                tile_count: 8,
+            }],
+        );
+        let mut loaded = vec![("tmx", tiled::from_tmx(&tmx).unwrap())];
+        for extension in ["ron", "json", "tmb"] {
+            loaded.push((extension, round_trip(&map, "corners", extension).unwrap()));
+        }
+
+        for (format, loaded) in loaded {
+            assert_eq!(loaded.get_dimensions(), (width, height), "{format}");
+            for (x, y) in [
+                (0, 0),
+                (width - 1, 0),
+                (0, height - 1),
+                (width - 1, height - 1),
+            ] {
+                assert_eq!(
+                    loaded.get_layer_tile(x, y, 0),
+                    Some(tile(x, y)),
+                    "({x}, {y}) in {format}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn binary_maps_without_tints_are_loaded() {
+        let content = bincode::serialize(&(
+            4u32,
+            2u16,
+            1u16,
+            vec![binary_layer()],
+            MapMetadata::default(),
+            BTreeMap::<u32, Properties>::new(),
+        ))
+        .unwrap();
+        let map = load_bytes(content, "no_tint").unwrap();
+
+        let tile = map.get_layer_tile(1, 0, 0).unwrap();
+        assert_eq!((tile.value, tile.rotation, tile.tint), (4, 3, None));
+    }
+}
@@ -0,0 +1,49 @@
+use iced::keyboard::{self, KeyCode};
+use iced_native::{event, Event};
+
+use crate::Message;
+
+/// Keyboard shortcuts available in the whole window
+pub fn shortcut(event: Event, status: event::Status) -> Option<Message> {
+    // the key was already used by a widget
+    if status == event::Status::Captured {
+        return None;
+    }
+
+    match event {
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code,
+            modifiers,
+        }) if modifiers.command() => match key_code {
+            KeyCode::N => Some(Message::NewMap),
+            KeyCode::O => Some(Message::OpenMap),
+            KeyCode::S => Some(Message::SaveMap),
+            KeyCode::R => Some(Message::RotateBrush),
+            KeyCode::F => Some(Message::FillSelection),
+            KeyCode::C => Some(Message::CopySelection),
+            // the next stamps paste the copied tiles mirrored
+            KeyCode::V if modifiers.shift() => Some(Message::MirrorClipboard(true)),
+            KeyCode::V if modifiers.alt() => Some(Message::MirrorClipboard(false)),
+            KeyCode::A => Some(Message::SelectAll),
+            KeyCode::I => Some(Message::InvertSelection),
+            _ => None,
+        },
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code,
+            modifiers,
+        }) => match key_code {
+            KeyCode::Delete => Some(Message::DeleteSelection),
+            KeyCode::Escape => Some(Message::Deselect),
+            KeyCode::Home => Some(Message::FitView),
+            KeyCode::LBracket => Some(Message::ChangeBrushSize(-1)),
+            KeyCode::RBracket => Some(Message::ChangeBrushSize(1)),
+            // with shift, the selection jumps by a chunk
+            KeyCode::Left => Some(Message::NudgeSelection(-1, 0, modifiers.shift())),
+            KeyCode::Right => Some(Message::NudgeSelection(1, 0, modifiers.shift())),
+            KeyCode::Up => Some(Message::NudgeSelection(0, -1, modifiers.shift())),
+            KeyCode::Down => Some(Message::NudgeSelection(0, 1, modifiers.shift())),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use iced::{
+    pure::{
+        checkbox, scrollable,
+        widget::{Button, Column, Row, Text},
+        Element,
+    },
+    Alignment, Length,
+};
+
+use crate::{tilemap::Tile, tileselector::TileSelector, Message, SheetEntry};
+
+/// Number of cells using each tile, shown below the toolbar while open
+#[derive(Default)]
+pub struct StatisticsDialog {
+    whole_map: bool,
+}
+
+impl StatisticsDialog {
+    /// Whether the tiles of every layer are counted, instead of the active one only
+    pub fn whole_map(&self) -> bool {
+        self.whole_map
+    }
+
+    pub fn set_whole_map(&mut self, whole_map: bool) {
+        self.whole_map = whole_map;
+    }
+
+    /// View of `counts`, the most used tiles first, each named after its sheet among `sheets`
+    pub fn view<'a>(
+        &self,
+        counts: HashMap<(u8, u32), u32>,
+        selector: &'a TileSelector,
+        sheets: Vec<SheetEntry>,
+    ) -> Element<'a, Message> {
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+
+        let list = counts.into_iter().fold(
+            Column::new().spacing(2),
+            |column, ((sheet, value), count)| {
+                let tile = Tile {
+                    sheet,
+                    ..Tile::new(value, false, false)
+                };
+                let name = match sheets.iter().find(|entry| entry.sheet == sheet) {
+                    Some(entry) => entry.name.clone(),
+                    None => format!("Sheet {}", sheet),
+                };
+
+                column.push(
+                    Row::new()
+                        .spacing(5)
+                        .align_items(Alignment::Center)
+                        .push(selector.brush_preview(Some(tile)))
+                        .push(
+                            Text::new(format!("{}, tile {}", name, value))
+                                .width(Length::Units(160)),
+                        )
+                        .push(Text::new(count.to_string()).width(Length::Units(60)))
+                        .push(
+                            Button::new(Text::new("Select"))
+                                .on_press(Message::SheetTileSelected(sheet, value)),
+                        ),
+                )
+            },
+        );
+
+        Column::new()
+            .spacing(5)
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(Text::new("Tile usage"))
+                    .push(checkbox(
+                        "Whole map",
+                        self.whole_map,
+                        Message::StatisticsWholeMap,
+                    ))
+                    .push(
+                        Button::new(Text::new("Close")).on_press(Message::StatisticsDialogClosed),
+                    ),
+            )
+            .push(scrollable(list).height(Length::Units(120)))
+            .into()
+    }
+}
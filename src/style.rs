@@ -1,4 +1,7 @@
-use iced::{pure::widget::button, Color};
+use iced::{
+    pure::widget::{button, container},
+    Color,
+};
 
 pub enum SelectorTheme {
     Selected,
@@ -35,3 +38,18 @@ impl button::StyleSheet for BtSelected {
         }
     }
 }
+
+/// Square filled with a colour, to preview it
+pub struct Swatch(pub Color);
+
+impl container::StyleSheet for Swatch {
+    fn style(&self) -> container::Style {
+        container::Style {
+            background: self.0.into(),
+            border_radius: 3.0,
+            border_width: 1.0,
+            border_color: Color::BLACK,
+            ..Default::default()
+        }
+    }
+}
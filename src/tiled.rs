@@ -0,0 +1,441 @@
+//! Interoperability with the [Tiled](https://www.mapeditor.org) TMX format
+
+use std::{fmt::Write, io};
+
+use crate::tilemap::{PropertyValue, Tile, TileMap};
+
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+/// Swaps the x and y axes, before the other flips
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+/// Every flag Tiled may store in the high bits of a GID
+const FLAGS: u32 = 0xF000_0000;
+
+/// GID of the first tile of the first tileset
+const FIRST_GID: u32 = 1;
+
+/// Tilesheet exported as a Tiled tileset
+pub struct Tileset {
+    pub name: String,
+    /// Number of frames of the sheet
+    pub tile_count: u32,
+}
+
+impl Tile {
+    /// Global tile id in Tiled, with the flip flags packed in the high bits
+    pub fn to_gid(self, first_gid: u32) -> u32 {
+        let target = transform(self.h_flip, self.v_flip, self.rotation);
+        let (diagonal, h_flip, v_flip) = FLIPS
+            .into_iter()
+            .find(|&(d, h, v)| tiled_transform(d, h, v) == target)
+            .unwrap_or_default();
+
+        let mut gid = self.value + first_gid;
+        if h_flip {
+            gid |= FLIPPED_HORIZONTALLY;
+        }
+        if v_flip {
+            gid |= FLIPPED_VERTICALLY;
+        }
+        if diagonal {
+            gid |= FLIPPED_DIAGONALLY;
+        }
+        gid
+    }
+
+    /// Tile from a Tiled global id, `None` for an empty cell
+    pub fn from_gid(gid: u32, first_gid: u32) -> Option<Tile> {
+        let value = (gid & !FLAGS).checked_sub(first_gid)?;
+
+        let target = tiled_transform(
+            gid & FLIPPED_DIAGONALLY != 0,
+            gid & FLIPPED_HORIZONTALLY != 0,
+            gid & FLIPPED_VERTICALLY != 0,
+        );
+        // favour flips over rotations
+        let (rotation, h_flip, v_flip) = (0..4)
+            .flat_map(|r| FLIPS[..4].iter().map(move |&(_, h, v)| (r, h, v)))
+            .find(|&(r, h, v)| transform(h, v, r) == target)
+            .unwrap_or_default();
+
+        Some(Tile {
+            rotation,
+            ..Tile::new(value, h_flip, v_flip)
+        })
+    }
+}
+
+/// Every combination of the diagonal, horizontal and vertical flips
+const FLIPS: [(bool, bool, bool); 8] = [
+    (false, false, false),
+    (false, true, false),
+    (false, false, true),
+    (false, true, true),
+    (true, false, false),
+    (true, true, false),
+    (true, false, true),
+    (true, true, true),
+];
+
+/// Image of an asymmetric point by the transform of a tile, to compare transforms
+fn transform(h_flip: bool, v_flip: bool, rotation: u8) -> (i32, i32) {
+    let (mut x, mut y) = (1, 2);
+    if h_flip {
+        x = -x;
+    }
+    if v_flip {
+        y = -y;
+    }
+    for _ in 0..rotation % 4 {
+        (x, y) = (-y, x);
+    }
+    (x, y)
+}
+
+/// Same as [`transform`], for the flips of Tiled
+fn tiled_transform(diagonal: bool, h_flip: bool, v_flip: bool) -> (i32, i32) {
+    let (mut x, mut y) = (1, 2);
+    if diagonal {
+        (x, y) = (y, x);
+    }
+    if h_flip {
+        x = -x;
+    }
+    if v_flip {
+        y = -y;
+    }
+    (x, y)
+}
+
+/// First GID and number of tiles of the tileset of each sheet, one tileset following the other.
+///
+/// A tileset holds at least the tiles of its sheet that the map uses, so that the GIDs of two
+/// sheets never overlap even if they are not loaded.
+fn tileset_ranges(map: &TileMap, sheets: &[Tileset]) -> Vec<(u32, u32)> {
+    let (width, height) = map.get_dimensions();
+    let mut counts: Vec<u32> = sheets.iter().map(|sheet| sheet.tile_count).collect();
+    if counts.is_empty() {
+        counts.push(0);
+    }
+
+    for layer in 0..map.layer_count() {
+        for y in 0..height {
+            for x in 0..width {
+                if let Some(tile) = map.get_layer_tile(x, y, layer) {
+                    let sheet = tile.sheet as usize;
+                    if counts.len() <= sheet {
+                        counts.resize(sheet + 1, 0);
+                    }
+                    counts[sheet] = counts[sheet].max(tile.value + 1);
+                }
+            }
+        }
+    }
+
+    let mut first_gid = FIRST_GID;
+    counts
+        .into_iter()
+        .map(|count| {
+            let range = (first_gid, count);
+            first_gid += count;
+            range
+        })
+        .collect()
+}
+
+/// Warning about the tiles of `map` which Tiled maps cannot keep as they are: animated and
+/// tinted tiles, since Tiled stores neither per cell
+pub fn lost_details(map: &TileMap) -> Option<String> {
+    let (width, height) = map.get_dimensions();
+    let (mut animated, mut tinted) = (0, 0);
+    for layer in 0..map.layer_count() {
+        for y in 0..height {
+            for x in 0..width {
+                if let Some(tile) = map.get_layer_tile(x, y, layer) {
+                    animated += tile.anim.is_some() as u32;
+                    tinted += tile.tint.is_some() as u32;
+                }
+            }
+        }
+    }
+
+    let lost: Vec<_> = [
+        (
+            animated,
+            "animated, exported as the first frame of their animation",
+        ),
+        (tinted, "tinted, exported without their tint"),
+    ]
+    .into_iter()
+    .filter(|&(count, _)| count > 0)
+    .map(|(count, loss)| match count {
+        1 => format!("1 tile is {}", loss),
+        count => format!("{} tiles are {}", count, loss),
+    })
+    .collect();
+
+    (!lost.is_empty()).then(|| format!("Tiled maps cannot keep every tile: {}.", lost.join(", ")))
+}
+
+/// Writes the map as a TMX map, with one tileset per sheet of `sheets` or used by the map
+pub fn to_tmx(map: &TileMap, tile_size: u16, sheets: &[Tileset]) -> String {
+    let (width, height) = map.get_dimensions();
+    let ranges = tileset_ranges(map, sheets);
+    let mut out = String::new();
+
+    // writing in a String cannot fail
+    let _ = writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        out,
+        r#"<map version="1.10" orientation="orthogonal" renderorder="right-down" width="{width}" height="{height}" tilewidth="{tile_size}" tileheight="{tile_size}" infinite="0" nextlayerid="{}" nextobjectid="1">"#,
+        map.layer_count() + 1
+    );
+    for (sheet, &(first_gid, tile_count)) in ranges.iter().enumerate() {
+        let name = sheets.get(sheet).map_or_else(
+            || format!("Sheet {}", sheet + 1),
+            |sheet| sheet.name.clone(),
+        );
+        let tileset = format!(
+            r#" <tileset firstgid="{first_gid}" name="{}" tilewidth="{tile_size}" tileheight="{tile_size}" tilecount="{tile_count}""#,
+            escape(&name)
+        );
+        // properties belong to a tile value, whatever its sheet
+        let properties: Vec<_> = map.tile_properties().range(..tile_count).collect();
+
+        if properties.is_empty() {
+            let _ = writeln!(out, "{}/>", tileset);
+            continue;
+        }
+        let _ = writeln!(out, "{}>", tileset);
+        for (value, properties) in properties {
+            let _ = writeln!(out, r#"  <tile id="{value}">"#);
+            let _ = writeln!(out, "   <properties>");
+            for (name, property) in properties {
+                let kind = match property {
+                    PropertyValue::Bool(_) => "bool",
+                    PropertyValue::String(_) => "string",
+                };
+                let _ = writeln!(
+                    out,
+                    r#"    <property name="{}" type="{kind}" value="{}"/>"#,
+                    escape(name),
+                    escape(&property.to_string())
+                );
+            }
+            let _ = writeln!(out, "   </properties>");
+            let _ = writeln!(out, "  </tile>");
+        }
+        let _ = writeln!(out, " </tileset>");
+    }
+
+    for layer in 0..map.layer_count() {
+        let _ = writeln!(
+            out,
+            r#" <layer id="{}" name="{}" width="{width}" height="{height}">"#,
+            layer + 1,
+            escape(map.layer_name(layer))
+        );
+        let _ = writeln!(out, r#"  <data encoding="csv">"#);
+
+        let rows: Vec<String> = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        map.get_layer_tile(x, y, layer)
+                            .map_or(0, |tile| tile.to_gid(ranges[tile.sheet as usize].0))
+                            .to_string()
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect();
+        let _ = writeln!(out, "{}", rows.join(",\n"));
+
+        let _ = writeln!(out, "</data>");
+        let _ = writeln!(out, " </layer>");
+    }
+
+    let _ = writeln!(out, "</map>");
+    out
+}
+
+/// Reads a TMX map, keeping its tile layers.
+///
+/// Only CSV encoded layers are supported.
+pub fn from_tmx(xml: &str) -> io::Result<TileMap> {
+    let document = roxmltree::Document::parse(xml).map_err(invalid)?;
+    let root = document.root_element();
+
+    if root.tag_name().name() != "map" {
+        return Err(invalid("not a Tiled map"));
+    }
+
+    let attribute = |node: roxmltree::Node, name: &str| -> io::Result<u32> {
+        node.attribute(name)
+            .ok_or_else(|| invalid(format!("missing attribute {}", name)))?
+            .parse()
+            .map_err(invalid)
+    };
+
+    let width = attribute(root, "width")? as u16;
+    let height = attribute(root, "height")? as u16;
+
+    // the tilesets are read as sheets, in the order of their first GID
+    let mut first_gids = root
+        .children()
+        .filter(|n| n.has_tag_name("tileset"))
+        .map(|tileset| attribute(tileset, "firstgid"))
+        .collect::<io::Result<Vec<_>>>()?;
+    first_gids.sort_unstable();
+    if first_gids.is_empty() {
+        first_gids.push(FIRST_GID);
+    }
+    let tile = |gid: u32| {
+        let sheet = first_gids
+            .iter()
+            .rposition(|&first_gid| first_gid <= gid & !FLAGS)
+            .unwrap_or_default();
+        Tile::from_gid(gid, first_gids[sheet]).map(|tile| Tile {
+            sheet: sheet as u8,
+            ..tile
+        })
+    };
+
+    let layers: Vec<_> = root
+        .children()
+        .filter(|n| n.has_tag_name("layer"))
+        .collect();
+    let mut out_map = TileMap::with_layers(
+        width,
+        height,
+        layers.iter().enumerate().map(|(id, node)| {
+            node.attribute("name")
+                .map_or_else(|| format!("Layer {}", id + 1), str::to_string)
+        }),
+    );
+
+    for (layer, node) in layers.into_iter().enumerate() {
+        let data = node
+            .children()
+            .find(|n| n.has_tag_name("data"))
+            .ok_or_else(|| invalid("layer without data"))?;
+
+        if data.attribute("encoding") != Some("csv") {
+            return Err(invalid("only CSV encoded layers are supported"));
+        }
+
+        let gids = data
+            .text()
+            .unwrap_or_default()
+            .split(',')
+            .map(|gid| gid.trim().parse::<u32>().map_err(invalid))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        if gids.len() != width as usize * height as usize {
+            return Err(invalid("layer size does not match the map"));
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                let gid = gids[x as usize + y as usize * width as usize];
+                out_map.set_tile(x, y, tile(gid), layer);
+            }
+        }
+    }
+
+    Ok(out_map)
+}
+
+/// Escapes `text` to be put in an attribute
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn invalid<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(sheet: u8, value: u32, h_flip: bool) -> Option<Tile> {
+        Some(Tile {
+            sheet,
+            ..Tile::new(value, h_flip, false)
+        })
+    }
+
+    #[test]
+    fn sheets_get_their_own_tileset() {
+        let mut map = TileMap::new(3, 2);
+        map.set_tile(0, 0, tile(0, 3, false), 0);
+        map.set_tile(1, 0, tile(1, 0, true), 0);
+        map.set_tile(2, 1, tile(1, 1, false), 1);
+        // a sheet which is not loaded
+        map.set_tile(0, 1, tile(2, 5, false), 1);
+
+        let sheets = [
+            Tileset {
+                name: "terrain".to_string(),
+                tile_count: 4,
+            },
+            Tileset {
+                name: "props".to_string(),
+                tile_count: 2,
+            },
+        ];
+        let xml = to_tmx(&map, 8, &sheets);
+
+        assert!(xml.contains(r#"firstgid="1" name="terrain""#));
+        assert!(xml.contains(r#"firstgid="5" name="props""#));
+        assert!(xml
+            .contains(r#"firstgid="7" name="Sheet 3" tilewidth="8" tileheight="8" tilecount="6""#));
+        assert_eq!(from_tmx(&xml).unwrap(), map);
+    }
+
+    #[test]
+    fn lost_details_are_reported() {
+        let mut map = TileMap::new(2, 2);
+        map.set_tile(0, 0, tile(0, 1, false), 0);
+        assert_eq!(lost_details(&map), None);
+
+        map.set_tile(
+            1,
+            0,
+            Some(Tile {
+                anim: Some(0),
+                ..Tile::new(2, false, false)
+            }),
+            0,
+        );
+        map.set_tile(
+            1,
+            1,
+            Some(Tile {
+                tint: Some([255, 0, 0]),
+                ..Tile::new(2, false, false)
+            }),
+            1,
+        );
+        map.set_tile(
+            0,
+            1,
+            Some(Tile {
+                tint: Some([0, 0, 255]),
+                ..Tile::new(2, false, false)
+            }),
+            1,
+        );
+        assert_eq!(
+            lost_details(&map).as_deref(),
+            Some("Tiled maps cannot keep every tile: 1 tile is animated, exported as the first frame of their animation, 2 tiles are tinted, exported without their tint.")
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use asefile::AsepriteFile;
+use iced::{
+    canvas::{Frame, Path},
+    Color, Point, Size,
+};
+
+use crate::tilemap::Tile;
+
+/// A tile frame decoded once, as rectangles of pixels of the same colour.
+///
+/// Transparent pixels have no rectangle, so that the layers below show through, and
+/// translucent ones are blended over them.
+pub struct TileImage {
+    /// Rectangles of each colour, as `(x, y, width, height)` in tile pixels
+    fills: Vec<(Color, Vec<[u16; 4]>)>,
+}
+
+impl TileImage {
+    fn new(tiles: &AsepriteFile, tile: Tile, tile_size: u16) -> Self {
+        // asefile resolves grayscale and indexed sheets, palette included, into RGBA
+        let image = tiles.frame(tile.value).image();
+        Self::from_pixels(tile, tile_size, |x, y| {
+            image.get_pixel(x as u32, y as u32).0
+        })
+    }
+
+    /// Image of `tile`, `frame_pixel` giving the colour of each pixel of its frame of
+    /// `tile_size` by `tile_size` pixels
+    fn from_pixels(
+        tile: Tile,
+        tile_size: u16,
+        frame_pixel: impl Fn(usize, usize) -> [u8; 4],
+    ) -> Self {
+        let size = tile_size as usize;
+        let [red, green, blue] = tile.tint.unwrap_or([255; 3]);
+        let tint = |channel: u8, by: u8| (channel as u16 * by as u16 / 255) as u8;
+        // every transparent pixel is the same, whatever its colour
+        let pixel_at = |x: usize, y: usize| match frame_pixel(x, y) {
+            [_, _, _, 0] => [0; 4],
+            [r, g, b, a] => [tint(r, red), tint(g, green), tint(b, blue), a],
+        };
+        let mut rectangles: HashMap<[u8; 4], Vec<[u16; 4]>> = HashMap::new();
+
+        for row in 0..size {
+            let y = if tile.v_flip { size - 1 - row } else { row };
+
+            // merge consecutive pixels of the same colour into a single rectangle
+            let mut column = 0;
+            while column < size {
+                let pixel = pixel_at(column, row);
+                let mut length = 1;
+                while column + length < size && pixel_at(column + length, row) == pixel {
+                    length += 1;
+                }
+
+                if pixel[3] == 0 {
+                    column += length;
+                    continue;
+                }
+
+                let x = if tile.h_flip {
+                    size - column - length
+                } else {
+                    column
+                };
+
+                let (mut x, mut y, mut width, mut height) = (x, y, length, 1);
+                for _ in 0..tile.rotation % 4 {
+                    // quarter turn clockwise, inside the tile
+                    (x, y, width, height) = (size - y - height, x, height, width);
+                }
+
+                rectangles.entry(pixel).or_default().push([
+                    x as u16,
+                    y as u16,
+                    width as u16,
+                    height as u16,
+                ]);
+
+                column += length;
+            }
+        }
+
+        TileImage {
+            fills: rectangles
+                .into_iter()
+                .map(|(pixel, rectangles)| {
+                    (
+                        Color::from_rgba8(pixel[0], pixel[1], pixel[2], pixel[3] as f32 / 255.0),
+                        rectangles,
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Draws the tile with its top left corner at `origin`, each of its pixels being
+    /// `pixel_side` wide.
+    ///
+    /// The edges of the pixels are rounded to whole pixels of the frame, the edge shared by
+    /// two pixels being rounded once for both. At fractional scales this keeps them sharp
+    /// without the antialiased seams left between rectangles meeting inside a pixel, at the
+    /// cost of pixels differing in size by one. The alpha of every pixel is multiplied by
+    /// `opacity`.
+    pub fn draw(&self, frame: &mut Frame, origin: Point, pixel_side: f32, opacity: f32) {
+        let edge = |start: f32, i: u16| edge(start, i, pixel_side);
+
+        for (colour, rectangles) in &self.fills {
+            let path = Path::new(|builder| {
+                for &[x, y, width, height] in rectangles {
+                    let (left, top) = (edge(origin.x, x), edge(origin.y, y));
+                    builder.rectangle(
+                        Point::new(left, top),
+                        Size::new(
+                            edge(origin.x, x + width) - left,
+                            edge(origin.y, y + height) - top,
+                        ),
+                    );
+                }
+            });
+
+            frame.fill(
+                &path,
+                Color {
+                    a: colour.a * opacity,
+                    ..*colour
+                },
+            );
+        }
+    }
+
+    /// Blends the tile over `pixels`, rows of `width` RGBA pixels, its top left corner at
+    /// `(left, top)` and each of its pixels on one of them. Pixels past the end are left out.
+    pub fn paint(&self, pixels: &mut [u8], width: usize, (left, top): (usize, usize)) {
+        for (colour, rectangles) in &self.fills {
+            for &[x, y, rectangle_width, rectangle_height] in rectangles {
+                let (x, y) = (left + x as usize, top + y as usize);
+                for row in y..y + rectangle_height as usize {
+                    for column in x..x + rectangle_width as usize {
+                        let start = (row * width + column) * 4;
+                        if let Some(pixel) = pixels.get_mut(start..start + 4) {
+                            blend(pixel, *colour);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Position of the edge before the pixel `i` of a tile starting at `start`, rounded to a whole
+/// pixel of the frame
+fn edge(start: f32, i: u16, pixel_side: f32) -> f32 {
+    (start + i as f32 * pixel_side).round()
+}
+
+/// Draws `colour` over the RGBA `pixel`
+fn blend(pixel: &mut [u8], colour: Color) {
+    let below = pixel[3] as f32 / 255.0 * (1.0 - colour.a);
+    let alpha = colour.a + below;
+    if alpha <= 0.0 {
+        return;
+    }
+
+    for (channel, over) in pixel.iter_mut().zip([colour.r, colour.g, colour.b]) {
+        let under = *channel as f32 / 255.0;
+        *channel = ((over * colour.a + under * below) / alpha * 255.0).round() as u8;
+    }
+    pixel[3] = (alpha * 255.0).round() as u8;
+}
+
+/// Decoded tiles, shared between redraws until the tilesheet changes
+#[derive(Default)]
+pub struct TileImages {
+    images: RefCell<HashMap<Tile, Rc<TileImage>>>,
+}
+
+impl TileImages {
+    /// Returns the image of `tile`, decoding it if needed.
+    ///
+    /// `tile.value` must be a valid frame of `tiles`.
+    pub fn get(&self, tiles: &AsepriteFile, tile: Tile, tile_size: u16) -> Rc<TileImage> {
+        self.images
+            .borrow_mut()
+            .entry(tile)
+            .or_insert_with(|| Rc::new(TileImage::new(tiles, tile, tile_size)))
+            .clone()
+    }
+
+    pub fn clear(&self) {
+        self.images.borrow_mut().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Top left corner of every pixel drawn, in tile pixels
+    fn pixels(image: &TileImage) -> Vec<(u16, u16)> {
+        let mut pixels: Vec<_> = image
+            .fills
+            .iter()
+            .flat_map(|(_, rectangles)| rectangles)
+            .flat_map(|&[x, y, width, height]| {
+                (y..y + height).flat_map(move |y| (x..x + width).map(move |x| (x, y)))
+            })
+            .collect();
+        pixels.sort_unstable();
+        pixels
+    }
+
+    #[test]
+    fn large_tiles_are_drawn_whole() {
+        let image = TileImage::from_pixels(Tile::new(0, false, false), 16, |_, _| [255, 0, 0, 255]);
+
+        let expected: Vec<_> = (0..16).flat_map(|x| (0..16).map(move |y| (x, y))).collect();
+        assert_eq!(pixels(&image), expected);
+    }
+
+    #[test]
+    fn large_tiles_are_transformed_inside_their_size() {
+        // a single pixel, in the top right corner
+        let corner = |x, y| match (x, y) {
+            (15, 0) => [0, 0, 255, 255],
+            _ => [0; 4],
+        };
+        let transformed = |tile| pixels(&TileImage::from_pixels(tile, 16, corner));
+
+        assert_eq!(transformed(Tile::new(0, false, false)), [(15, 0)]);
+        assert_eq!(transformed(Tile::new(0, true, false)), [(0, 0)]);
+        assert_eq!(transformed(Tile::new(0, false, true)), [(15, 15)]);
+        assert_eq!(
+            transformed(Tile {
+                rotation: 1,
+                ..Tile::new(0, false, false)
+            }),
+            [(15, 15)]
+        );
+        assert_eq!(
+            transformed(Tile {
+                rotation: 3,
+                ..Tile::new(0, false, false)
+            }),
+            [(0, 0)]
+        );
+    }
+
+    #[test]
+    fn neighbouring_rectangles_share_their_edges() {
+        for pixel_side in [0.7, 1.0, 1.37, 2.5, 3.3] {
+            for start in [0.0, 10.4, 33.5] {
+                // two rectangles meeting at the pixel 3, drawn apart
+                let first = (edge(start, 0, pixel_side), edge(start, 3, pixel_side));
+                let second = (edge(start, 3, pixel_side), edge(start, 8, pixel_side));
+
+                assert_eq!(first.1, second.0, "side {pixel_side}, from {start}");
+                for position in [first.0, first.1, second.1] {
+                    assert_eq!(position.fract(), 0.0, "side {pixel_side}, from {start}");
+                }
+                // no pixel more than one off its size
+                for i in 0..8 {
+                    let side = edge(start, i + 1, pixel_side) - edge(start, i, pixel_side);
+                    assert!(
+                        (side - pixel_side).abs() <= 1.0,
+                        "side {pixel_side}, pixel {i}"
+                    );
+                }
+            }
+        }
+    }
+}
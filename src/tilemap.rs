@@ -1,60 +1,171 @@
-use std::{fmt::Display, iter};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TileMap {
-    background: LayerContent,
-    foreground: LayerContent,
+    /// Shared by every layer, whose tiles are always `width` by `height`
+    width: u16,
+    height: u16,
+    /// From the bottom to the top, there is always at least one
+    layers: Vec<LayerContent>,
+    metadata: MapMetadata,
+    /// Properties of the tiles which have some, by tile value
+    tile_properties: BTreeMap<u32, Properties>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Layer {
-    Background,
-    Foreground,
+/// Description of a map, which has no effect on its tiles
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapMetadata {
+    pub name: String,
+    pub author: String,
+    pub description: String,
 }
 
-impl Layer {
-    pub const ALL: [Layer; 2] = [Layer::Background, Layer::Foreground];
+/// Value of a custom property of a tile, for the game to read
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PropertyValue {
+    Bool(bool),
+    String(String),
 }
 
-impl Display for Layer {
+impl Display for PropertyValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Layer::Background => "Background",
-                Layer::Foreground => "Foreground",
-            }
-        )
+        match self {
+            PropertyValue::Bool(value) => write!(f, "{}", value),
+            PropertyValue::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Custom properties of a tile, by name
+pub type Properties = BTreeMap<String, PropertyValue>;
+
+impl MapMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty() && self.author.is_empty() && self.description.is_empty()
     }
 }
 
-#[derive(Debug, Clone)]
+/// Index of a layer of the map, from the bottom
+pub type Layer = usize;
+
+/// Layer of a map, displayable in a pick list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerEntry {
+    pub layer: Layer,
+    pub name: String,
+}
+
+impl Display for LayerEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct LayerContent {
-    width: u16,
-    height: u16,
-    tiles: Vec<Vec<Option<Tile>>>,
+    name: String,
+    /// Row after row, see [`index`]
+    tiles: Vec<Option<Tile>>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Tile {
+    /// Index of the tilesheet the tile comes from
+    #[serde(default)]
+    pub sheet: u8,
     pub value: u32,
     pub h_flip: bool,
     pub v_flip: bool,
+    /// Number of clockwise quarter turns, applied after the flips
+    #[serde(default)]
+    pub rotation: u8,
+    /// Aseprite tag played instead of `value`, which stays the first frame of the tag
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anim: Option<u32>,
+    /// Red, green and blue the colours of the tile are multiplied by, out of 255
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tint: Option<[u8; 3]>,
 }
 
 impl Tile {
     pub fn new(value: u32, h_flip: bool, v_flip: bool) -> Self {
         Tile {
+            sheet: 0,
             value,
             h_flip,
             v_flip,
+            rotation: 0,
+            anim: None,
+            tint: None,
+        }
+    }
+
+    /// Same tile, mirrored left to right after its own transform
+    pub fn flipped_horizontally(self) -> Self {
+        // mirroring then turning is the same as turning the other way then mirroring
+        Tile {
+            h_flip: !self.h_flip,
+            rotation: (4 - self.rotation % 4) % 4,
+            ..self
+        }
+    }
+
+    /// Same tile, mirrored top to bottom after its own transform
+    pub fn flipped_vertically(self) -> Self {
+        Tile {
+            v_flip: !self.v_flip,
+            rotation: (4 - self.rotation % 4) % 4,
+            ..self
+        }
+    }
+
+    /// Same tile, turned by `quarter_turns` clockwise after its own transform
+    fn rotated(self, quarter_turns: u8) -> Self {
+        Tile {
+            rotation: (self.rotation + quarter_turns) % 4,
+            ..self
         }
     }
 }
 
+/// Transform of the whole map, its tiles being transformed along
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapTransform {
+    FlipHorizontal,
+    FlipVertical,
+    RotateClockwise,
+    RotateCounterClockwise,
+}
+
+impl MapTransform {
+    pub const ALL: [MapTransform; 4] = [
+        MapTransform::FlipHorizontal,
+        MapTransform::FlipVertical,
+        MapTransform::RotateClockwise,
+        MapTransform::RotateCounterClockwise,
+    ];
+}
+
+impl Display for MapTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                MapTransform::FlipHorizontal => "Flip horizontally",
+                MapTransform::FlipVertical => "Flip vertically",
+                MapTransform::RotateClockwise => "Rotate clockwise",
+                MapTransform::RotateCounterClockwise => "Rotate counter-clockwise",
+            }
+        )
+    }
+}
+
 impl Default for TileMap {
     fn default() -> Self {
         TileMap::new(32, 32)
@@ -62,90 +173,910 @@ impl Default for TileMap {
 }
 
 impl TileMap {
+    /// Empty map with a background and a foreground layer
     pub fn new(width: u16, height: u16) -> Self {
+        TileMap::with_layers(
+            width,
+            height,
+            ["Background".to_string(), "Foreground".to_string()],
+        )
+    }
+
+    /// Empty map with one layer per name, or a single one if there are none
+    pub fn with_layers(width: u16, height: u16, names: impl IntoIterator<Item = String>) -> Self {
+        let mut layers: Vec<LayerContent> = names
+            .into_iter()
+            .map(|name| LayerContent::new(name, width, height))
+            .collect();
+        if layers.is_empty() {
+            layers.push(LayerContent::new("Background".to_string(), width, height));
+        }
+
         TileMap {
-            background: LayerContent::new(width, height),
-            foreground: LayerContent::new(width, height),
+            width,
+            height,
+            layers,
+            metadata: Default::default(),
+            tile_properties: BTreeMap::new(),
         }
     }
 
     pub fn set_tile(&mut self, x: u16, y: u16, value: Option<Tile>, layer: Layer) {
-        match layer {
-            Layer::Background => self.background.set_tile(x, y, value),
-            Layer::Foreground => self.foreground.set_tile(x, y, value),
+        let dimensions = self.get_dimensions();
+        if let Some(content) = self.layers.get_mut(layer) {
+            content.set_tile(dimensions, x, y, value);
         }
     }
 
-    pub fn get_tile(&self, x: u16, y: u16) -> (Option<Tile>, Option<Tile>) {
-        (
-            self.background.get_tile(x, y),
-            self.foreground.get_tile(x, y),
-        )
+    pub fn get_layer_tile(&self, x: u16, y: u16, layer: Layer) -> Option<Tile> {
+        self.layers
+            .get(layer)?
+            .get_tile(self.get_dimensions(), x, y)
+    }
+
+    /// Cells of each layer holding another tile in `other`.
+    ///
+    /// The maps may differ in size or number of layers, the cells out of one of them counting as
+    /// empty in it.
+    pub fn diff(&self, other: &TileMap) -> Vec<(u16, u16, Layer)> {
+        let (width, height) = self.get_dimensions();
+        let (other_width, other_height) = other.get_dimensions();
+        let (width, height) = (width.max(other_width), height.max(other_height));
+
+        (0..self.layer_count().max(other.layer_count()))
+            .flat_map(|layer| (0..height).flat_map(move |y| (0..width).map(move |x| (x, y, layer))))
+            .filter(|&(x, y, layer)| {
+                self.get_layer_tile(x, y, layer) != other.get_layer_tile(x, y, layer)
+            })
+            .collect()
+    }
+
+    pub fn metadata(&self) -> &MapMetadata {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: MapMetadata) {
+        self.metadata = metadata;
+    }
+
+    pub fn tile_properties(&self) -> &BTreeMap<u32, Properties> {
+        &self.tile_properties
+    }
+
+    pub fn set_tile_properties(&mut self, tile_properties: BTreeMap<u32, Properties>) {
+        self.tile_properties = tile_properties;
+    }
+
+    /// Sets the property `name` of the tile `value`, removing it if `property` is `None`
+    pub fn set_tile_property(&mut self, value: u32, name: String, property: Option<PropertyValue>) {
+        match property {
+            Some(property) => {
+                self.tile_properties
+                    .entry(value)
+                    .or_default()
+                    .insert(name, property);
+            }
+            None => {
+                if let Some(properties) = self.tile_properties.get_mut(&value) {
+                    properties.remove(&name);
+                    if properties.is_empty() {
+                        self.tile_properties.remove(&value);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn layer_name(&self, layer: Layer) -> &str {
+        self.layers.get(layer).map_or("", |content| &content.name)
+    }
+
+    /// Every layer, from the bottom
+    pub fn layers(&self) -> Vec<LayerEntry> {
+        self.layers
+            .iter()
+            .enumerate()
+            .map(|(layer, content)| LayerEntry {
+                layer,
+                name: content.name.clone(),
+            })
+            .collect()
+    }
+
+    /// Inserts an empty layer at `layer`, below the ones from `layer` upwards
+    pub fn add_layer(&mut self, layer: Layer, name: String) {
+        let (width, height) = self.get_dimensions();
+        self.layers.insert(
+            layer.min(self.layers.len()),
+            LayerContent::new(name, width, height),
+        );
+    }
+
+    /// Removes `layer`, unless it is the last one
+    pub fn remove_layer(&mut self, layer: Layer) {
+        if self.layers.len() > 1 && layer < self.layers.len() {
+            self.layers.remove(layer);
+        }
+    }
+
+    /// Exchanges the layers `a` and `b`, with their names
+    pub fn swap_layers(&mut self, a: Layer, b: Layer) {
+        if a < self.layers.len() && b < self.layers.len() {
+            self.layers.swap(a, b);
+        }
+    }
+
+    /// Overwrites every cell of `to` with the one of `from`
+    pub fn copy_layer(&mut self, from: Layer, to: Layer) {
+        if from != to && to < self.layers.len() {
+            if let Some(tiles) = self.layers.get(from).map(|content| content.tiles.clone()) {
+                self.layers[to].tiles = tiles;
+            }
+        }
+    }
+
+    /// Cells of `layer` holding the same tile value as `(x, y)`, wherever they are
+    pub fn matching(&self, x: u16, y: u16, layer: Layer) -> HashSet<(u16, u16)> {
+        let (width, height) = self.get_dimensions();
+        let content = match self.layers.get(layer) {
+            Some(content) if x < width && y < height => content,
+            _ => return HashSet::new(),
+        };
+
+        let value = |x: u16, y: u16| {
+            content
+                .get_tile((width, height), x, y)
+                .map(|tile| (tile.sheet, tile.value))
+        };
+        let target = value(x, y);
+
+        (0..width)
+            .flat_map(|x| (0..height).map(move |y| (x, y)))
+            .filter(|&(x, y)| value(x, y) == target)
+            .collect()
+    }
+
+    /// Cells of `layer` connected to `(x, y)` by their sides, all holding the same tile value
+    pub fn region(&self, x: u16, y: u16, layer: Layer) -> HashSet<(u16, u16)> {
+        let (width, height) = self.get_dimensions();
+        let content = match self.layers.get(layer) {
+            Some(content) if x < width && y < height => content,
+            _ => return HashSet::new(),
+        };
+
+        let value = |x: u16, y: u16| {
+            content
+                .get_tile((width, height), x, y)
+                .map(|tile| (tile.sheet, tile.value))
+        };
+        let target = value(x, y);
+
+        self.flood(x, y, |x, y| value(x, y) == target)
+    }
+
+    /// Empty cells connected to `(x, y)` by their sides, the tiles of every layer acting as walls
+    pub fn enclosed_region(&self, x: u16, y: u16) -> HashSet<(u16, u16)> {
+        let (width, height) = self.get_dimensions();
+        if x >= width || y >= height {
+            return HashSet::new();
+        }
+
+        let empty = |x: u16, y: u16| {
+            (0..self.layer_count()).all(|layer| self.get_layer_tile(x, y, layer).is_none())
+        };
+        if !empty(x, y) {
+            return HashSet::new();
+        }
+
+        self.flood(x, y, empty)
+    }
+
+    /// Cells connected to `(x, y)` by their sides for which `inside` holds, which it must for
+    /// `(x, y)`
+    fn flood(&self, x: u16, y: u16, inside: impl Fn(u16, u16) -> bool) -> HashSet<(u16, u16)> {
+        let (width, height) = self.get_dimensions();
+        let mut region = HashSet::new();
+
+        let mut pending = vec![(x, y)];
+        while let Some((x, y)) = pending.pop() {
+            if !inside(x, y) || !region.insert((x, y)) {
+                continue;
+            }
+
+            if x > 0 {
+                pending.push((x - 1, y));
+            }
+            if x + 1 < width {
+                pending.push((x + 1, y));
+            }
+            if y > 0 {
+                pending.push((x, y - 1));
+            }
+            if y + 1 < height {
+                pending.push((x, y + 1));
+            }
+        }
+
+        region
+    }
+
+    /// Empties every cell of `layer`
+    pub fn clear_layer(&mut self, layer: Layer) {
+        self.fill_layer(None, layer);
+    }
+
+    /// Sets every cell of `layer` to `tile`
+    pub fn fill_layer(&mut self, tile: Option<Tile>, layer: Layer) {
+        if let Some(content) = self.layers.get_mut(layer) {
+            content.tiles.fill(tile);
+        }
+    }
+
+    /// Moves the tiles of `layer` by `(dx, dy)`, see [`LayerContent::offset`]
+    pub fn offset_layer(&mut self, dx: i32, dy: i32, wrap: bool, layer: Layer) {
+        let dimensions = self.get_dimensions();
+        if let Some(content) = self.layers.get_mut(layer) {
+            content.offset(dimensions, dx, dy, wrap);
+        }
+    }
+
+    /// Replaces the static tiles `from`, as `(sheet, value)`, by `to` in `layer`, or in every
+    /// layer if `None`.
+    ///
+    /// Flips and rotations of the tiles are kept.
+    pub fn replace_tile(&mut self, from: (u8, u32), to: (u8, u32), layer: Option<Layer>) {
+        for (id, content) in self.layers.iter_mut().enumerate() {
+            if layer.is_some_and(|layer| layer != id) {
+                continue;
+            }
+
+            for tile in content.tiles.iter_mut().flatten() {
+                if (tile.sheet, tile.value) == from && tile.anim.is_none() {
+                    (tile.sheet, tile.value) = to;
+                }
+            }
+        }
+    }
+
+    pub fn transform(&mut self, transform: MapTransform) {
+        let dimensions = self.get_dimensions();
+        for layer in &mut self.layers {
+            match transform {
+                MapTransform::FlipHorizontal => layer.flip_horizontal(dimensions),
+                MapTransform::FlipVertical => layer.flip_vertical(dimensions),
+                MapTransform::RotateClockwise => layer.rotate_cw(dimensions),
+                MapTransform::RotateCounterClockwise => layer.rotate_ccw(dimensions),
+            }
+        }
+
+        if matches!(
+            transform,
+            MapTransform::RotateClockwise | MapTransform::RotateCounterClockwise
+        ) {
+            (self.width, self.height) = (self.height, self.width);
+        }
     }
 
+    #[allow(dead_code)] // no UI for resizing yet
     pub fn resize(&mut self, new_width: u16, new_height: u16) {
-        self.background.resize(new_width, new_height);
-        self.foreground.resize(new_width, new_height);
+        let dimensions = self.get_dimensions();
+        for layer in &mut self.layers {
+            layer.resize(dimensions, (new_width, new_height));
+        }
+        self.width = new_width;
+        self.height = new_height;
+    }
+
+    /// Smallest rectangle holding every tile of every layer, as `(x, y, width, height)`, `None`
+    /// if the map is empty
+    pub fn bounding_box(&self) -> Option<(u16, u16, u16, u16)> {
+        let (width, height) = self.get_dimensions();
+        let filled = |x: u16, y: u16| {
+            (0..self.layer_count()).any(|layer| self.get_layer_tile(x, y, layer).is_some())
+        };
+
+        let columns: Vec<u16> = (0..width)
+            .filter(|&x| (0..height).any(|y| filled(x, y)))
+            .collect();
+        let rows: Vec<u16> = (0..height)
+            .filter(|&y| (0..width).any(|x| filled(x, y)))
+            .collect();
+
+        let (&min_x, &max_x) = (columns.first()?, columns.last()?);
+        let (&min_y, &max_y) = (rows.first()?, rows.last()?);
+        Some((min_x, min_y, max_x - min_x + 1, max_y - min_y + 1))
+    }
+
+    /// Trims the empty borders of the map, leaving it untouched if it is empty.
+    ///
+    /// Returns whether the map was cropped.
+    pub fn crop_to_content(&mut self) -> bool {
+        let dimensions = self.get_dimensions();
+
+        match self.bounding_box() {
+            Some(rect) if rect != (0, 0, dimensions.0, dimensions.1) => {
+                for layer in &mut self.layers {
+                    layer.crop(dimensions, rect);
+                }
+                (self.width, self.height) = (rect.2, rect.3);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Number of cells holding each tile, by sheet and value, in `layer` or in every layer if `None`
+    pub fn tile_counts(&self, layer: Option<Layer>) -> HashMap<(u8, u32), u32> {
+        let mut counts = HashMap::new();
+
+        for (id, content) in self.layers.iter().enumerate() {
+            if layer.is_some_and(|layer| layer != id) {
+                continue;
+            }
+
+            for tile in content.tiles.iter().flatten() {
+                *counts.entry((tile.sheet, tile.value)).or_insert(0) += 1;
+            }
+        }
+
+        counts
+    }
+
+    /// Whether any tile of the map is animated
+    pub fn is_animated(&self) -> bool {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.tiles.iter().flatten())
+            .any(|tile| tile.anim.is_some())
     }
 
     pub fn get_dimensions(&self) -> (u16, u16) {
-        (self.background.width, self.background.height)
-        // need only to return one since they are always equal
+        debug_assert!(self
+            .layers
+            .iter()
+            .all(|layer| layer.tiles.len() == self.width as usize * self.height as usize));
+        (self.width, self.height)
     }
 }
 
+/// Index of `(x, y)` in the tiles of a layer of `(width, height)`, `None` if it is outside of it
+fn index((width, height): (u16, u16), x: u16, y: u16) -> Option<usize> {
+    (x < width && y < height).then(|| y as usize * width as usize + x as usize)
+}
+
+/// Tiles of a layer of `(width, height)`, `source` giving the one of each cell
+fn layout(
+    (width, height): (u16, u16),
+    source: impl Fn(u16, u16) -> Option<Tile>,
+) -> Vec<Option<Tile>> {
+    let mut tiles = Vec::with_capacity(width as usize * height as usize);
+    for y in 0..height {
+        tiles.extend((0..width).map(|x| source(x, y)));
+    }
+    tiles
+}
+
 impl LayerContent {
-    fn new(width: u16, height: u16) -> Self {
+    fn new(name: String, width: u16, height: u16) -> Self {
         LayerContent {
-            width,
-            height,
-            tiles: Vec::from_iter(
-                iter::repeat(Vec::from_iter(iter::repeat(None).take(height.into())))
-                    .take(width.into()),
-            ),
+            name,
+            tiles: vec![None; width as usize * height as usize],
         }
     }
 
-    fn set_tile(&mut self, x: u16, y: u16, value: Option<Tile>) {
-        let x = x as usize;
-        let y = y as usize;
+    /// Does nothing if `(x, y)` is outside of the layer, of `dimensions`
+    fn set_tile(&mut self, dimensions: (u16, u16), x: u16, y: u16, value: Option<Tile>) {
+        if let Some(tile) = index(dimensions, x, y).and_then(|i| self.tiles.get_mut(i)) {
+            *tile = value;
+        }
+    }
 
-        self.tiles[x][y] = value;
+    /// Returns `None` if `(x, y)` is outside of the layer, of `dimensions`
+    fn get_tile(&self, dimensions: (u16, u16), x: u16, y: u16) -> Option<Tile> {
+        self.tiles.get(index(dimensions, x, y)?).copied().flatten()
     }
 
-    fn get_tile(&self, x: u16, y: u16) -> Option<Tile> {
-        let x = x as usize;
-        let y = y as usize;
+    /// Moves every tile by `(dx, dy)`.
+    ///
+    /// Tiles pushed off an edge come back from the opposite one if `wrap` is set, and are lost
+    /// otherwise, vacated cells being left empty.
+    fn offset(&mut self, dimensions: (u16, u16), dx: i32, dy: i32, wrap: bool) {
+        let source = |x: u16, d: i32, side: u16| {
+            let (x, side) = (x as i32 - d, side as i32);
+            if wrap {
+                Some(x.rem_euclid(side) as u16)
+            } else {
+                (0..side).contains(&x).then_some(x as u16)
+            }
+        };
 
-        self.tiles[x][y]
+        self.tiles = layout(dimensions, |x, y| {
+            let x = source(x, dx, dimensions.0)?;
+            let y = source(y, dy, dimensions.1)?;
+            self.get_tile(dimensions, x, y)
+        });
     }
 
-    fn resize(&mut self, new_width: u16, new_height: u16) {
-        // first take care of height
-        if new_height > self.height {
-            // grow
-            self.tiles.extend(
-                iter::repeat(Vec::from_iter(iter::repeat(None).take(new_width as usize)))
-                    .take((new_height - self.height).into()),
-            )
+    fn map_tiles(&mut self, f: impl Fn(Tile) -> Tile) {
+        for tile in &mut self.tiles {
+            *tile = tile.map(&f);
+        }
+    }
+
+    fn flip_horizontal(&mut self, dimensions: (u16, u16)) {
+        self.tiles = layout(dimensions, |x, y| {
+            self.get_tile(dimensions, dimensions.0 - 1 - x, y)
+        });
+        self.map_tiles(Tile::flipped_horizontally);
+    }
+
+    fn flip_vertical(&mut self, dimensions: (u16, u16)) {
+        self.tiles = layout(dimensions, |x, y| {
+            self.get_tile(dimensions, x, dimensions.1 - 1 - y)
+        });
+        self.map_tiles(Tile::flipped_vertically);
+    }
+
+    /// The layer becomes `dimensions` swapped
+    fn rotate_cw(&mut self, (width, height): (u16, u16)) {
+        // (x, y) moves to (height - 1 - y, x)
+        self.tiles = layout((height, width), |x, y| {
+            self.get_tile((width, height), y, height - 1 - x)
+        });
+        self.map_tiles(|tile| tile.rotated(1));
+    }
+
+    /// The layer becomes `dimensions` swapped
+    fn rotate_ccw(&mut self, (width, height): (u16, u16)) {
+        // (x, y) moves to (y, width - 1 - x)
+        self.tiles = layout((height, width), |x, y| {
+            self.get_tile((width, height), width - 1 - y, x)
+        });
+        self.map_tiles(|tile| tile.rotated(3));
+    }
+
+    /// Keeps the tiles of the rectangle `(x, y, width, height)`, which must be in the layer
+    fn crop(&mut self, dimensions: (u16, u16), (x, y, width, height): (u16, u16, u16, u16)) {
+        self.tiles = layout((width, height), |cx, cy| {
+            self.get_tile(dimensions, x + cx, y + cy)
+        });
+    }
+
+    /// Keeps the tiles of the top left corner, filling the space added with empty cells
+    fn resize(&mut self, dimensions: (u16, u16), (new_width, new_height): (u16, u16)) {
+        if new_width == dimensions.0 {
+            // whole rows are added or removed at the end, in place
+            self.tiles
+                .resize(new_width as usize * new_height as usize, None);
         } else {
-            // height
-            self.tiles.truncate(new_height as usize);
+            self.tiles = layout((new_width, new_height), |x, y| {
+                self.get_tile(dimensions, x, y)
+            });
         }
-        self.height = new_height;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // then width
-        if new_width > self.width {
-            // grow
-            for row in &mut self.tiles[..] {
-                row.extend(iter::repeat(None).take((new_width - self.width).into()))
+    /// Map of two layers, every cell holding a tile numbered after its position
+    fn numbered(width: u16, height: u16) -> TileMap {
+        let mut map = TileMap::new(width, height);
+        for layer in 0..map.layer_count() {
+            for y in 0..height {
+                for x in 0..width {
+                    map.set_tile(x, y, Some(number(x, y, layer)), layer);
+                }
             }
-        } else {
-            // shrink
-            for row in &mut self.tiles[..] {
-                row.truncate(new_width as usize);
+        }
+        map
+    }
+
+    /// Tile of `(x, y)` on `layer` in [`numbered`] maps
+    fn number(x: u16, y: u16, layer: Layer) -> Tile {
+        Tile::new(
+            layer as u32 * 1000 + y as u32 * 100 + x as u32,
+            false,
+            false,
+        )
+    }
+
+    #[test]
+    fn cells_outside_of_the_map_are_ignored() {
+        let mut map = numbered(4, 3);
+        let unchanged = map.clone();
+
+        map.set_tile(4, 3, Some(Tile::new(7, false, false)), 0);
+        map.set_tile(4, 0, Some(Tile::new(7, false, false)), 0);
+        map.set_tile(0, 3, Some(Tile::new(7, false, false)), 1);
+        map.set_tile(u16::MAX, u16::MAX, None, 1);
+
+        assert_eq!(map, unchanged);
+        assert_eq!(map.get_layer_tile(4, 3, 0), None);
+        assert_eq!(map.get_layer_tile(0, 3, 1), None);
+    }
+
+    #[test]
+    fn clearing_a_layer_keeps_the_dimensions() {
+        let mut map = numbered(5, 3);
+        map.clear_layer(1);
+
+        assert_eq!(map.get_dimensions(), (5, 3));
+        for y in 0..3 {
+            for x in 0..5 {
+                assert_eq!(map.get_layer_tile(x, y, 0), Some(number(x, y, 0)));
+                assert_eq!(map.get_layer_tile(x, y, 1), None);
             }
         }
-        self.width = new_width;
+    }
+
+    /// Every cell of `layer`, row after row
+    fn cells(map: &TileMap, layer: Layer) -> Vec<Option<Tile>> {
+        let (width, height) = map.get_dimensions();
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| map.get_layer_tile(x, y, layer))
+            .collect()
+    }
+
+    #[test]
+    fn offsetting_moves_the_tiles_in_every_direction() {
+        let (width, height) = (4, 3);
+
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 2), (0, -1), (-3, 5)] {
+            for wrap in [false, true] {
+                let mut map = numbered(width, height);
+                map.offset_layer(dx, dy, wrap, 0);
+
+                // where each tile is expected to go
+                let mut expected = TileMap::new(width, height);
+                for y in 0..height {
+                    for x in 0..width {
+                        let (mut to_x, mut to_y) = (x as i32 + dx, y as i32 + dy);
+                        if wrap {
+                            to_x = to_x.rem_euclid(width as i32);
+                            to_y = to_y.rem_euclid(height as i32);
+                        }
+                        if let (Ok(to_x), Ok(to_y)) = (u16::try_from(to_x), u16::try_from(to_y)) {
+                            expected.set_tile(to_x, to_y, Some(number(x, y, 0)), 0);
+                        }
+                    }
+                }
+
+                assert_eq!(
+                    cells(&map, 0),
+                    cells(&expected, 0),
+                    "by ({dx}, {dy}), wrap {wrap}"
+                );
+                // the other layers stay in place
+                assert_eq!(cells(&map, 1), cells(&numbered(width, height), 1));
+            }
+        }
+    }
+
+    #[test]
+    fn transforming_moves_and_transforms_the_tiles() {
+        let (width, height) = (4, 3);
+
+        for transform in MapTransform::ALL {
+            let mut map = numbered(width, height);
+            map.transform(transform);
+
+            let rotated = matches!(
+                transform,
+                MapTransform::RotateClockwise | MapTransform::RotateCounterClockwise
+            );
+            let dimensions = if rotated {
+                (height, width)
+            } else {
+                (width, height)
+            };
+            assert_eq!(map.get_dimensions(), dimensions, "{transform}");
+
+            for layer in 0..2 {
+                for y in 0..height {
+                    for x in 0..width {
+                        let tile = number(x, y, layer);
+                        let ((to_x, to_y), expected) = match transform {
+                            MapTransform::FlipHorizontal => {
+                                ((width - 1 - x, y), tile.flipped_horizontally())
+                            }
+                            MapTransform::FlipVertical => {
+                                ((x, height - 1 - y), tile.flipped_vertically())
+                            }
+                            MapTransform::RotateClockwise => ((height - 1 - y, x), tile.rotated(1)),
+                            MapTransform::RotateCounterClockwise => {
+                                ((y, width - 1 - x), tile.rotated(3))
+                            }
+                        };
+
+                        assert_eq!(
+                            map.get_layer_tile(to_x, to_y, layer),
+                            Some(expected),
+                            "{transform} of ({x}, {y})"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn transforming_keeps_the_flags_of_the_tiles() {
+        let tile = Tile::new(1, true, false).rotated(1);
+
+        let mut map = TileMap::new(1, 1);
+        map.set_tile(0, 0, Some(tile), 0);
+        map.transform(MapTransform::FlipHorizontal);
+
+        let flipped = map.get_layer_tile(0, 0, 0).unwrap();
+        assert_eq!(
+            (flipped.h_flip, flipped.v_flip, flipped.rotation),
+            (false, false, 3)
+        );
+
+        map.transform(MapTransform::FlipHorizontal);
+        assert_eq!(map.get_layer_tile(0, 0, 0), Some(tile));
+
+        for _ in 0..4 {
+            map.transform(MapTransform::RotateClockwise);
+        }
+        assert_eq!(map.get_layer_tile(0, 0, 0), Some(tile));
+    }
+
+    #[test]
+    fn replacing_keeps_flips_and_skips_animations() {
+        let flipped = Tile {
+            rotation: 2,
+            ..Tile::new(5, true, false)
+        };
+        let animated = Tile {
+            anim: Some(0),
+            ..Tile::new(5, false, false)
+        };
+
+        // the same frame of another sheet
+        let other_sheet = Tile {
+            sheet: 1,
+            ..Tile::new(5, false, false)
+        };
+
+        let mut map = TileMap::new(4, 1);
+        map.set_tile(0, 0, Some(flipped), 0);
+        map.set_tile(1, 0, Some(animated), 0);
+        map.set_tile(2, 0, Some(Tile::new(6, false, true)), 0);
+        map.set_tile(3, 0, Some(other_sheet), 0);
+        map.set_tile(0, 0, Some(Tile::new(5, false, false)), 1);
+
+        let mut only_first = map.clone();
+        only_first.replace_tile((0, 5), (0, 9), Some(0));
+        assert_eq!(
+            only_first.get_layer_tile(0, 0, 0),
+            Some(Tile {
+                value: 9,
+                ..flipped
+            })
+        );
+        assert_eq!(only_first.get_layer_tile(1, 0, 0), Some(animated));
+        assert_eq!(
+            only_first.get_layer_tile(2, 0, 0),
+            Some(Tile::new(6, false, true))
+        );
+        assert_eq!(
+            only_first.get_layer_tile(0, 0, 1),
+            Some(Tile::new(5, false, false))
+        );
+
+        assert_eq!(only_first.get_layer_tile(3, 0, 0), Some(other_sheet));
+
+        map.replace_tile((0, 5), (0, 9), None);
+        assert_eq!(
+            map.get_layer_tile(0, 0, 0),
+            Some(Tile {
+                value: 9,
+                ..flipped
+            })
+        );
+        assert_eq!(map.get_layer_tile(1, 0, 0), Some(animated));
+        assert_eq!(
+            map.get_layer_tile(0, 0, 1),
+            Some(Tile::new(9, false, false))
+        );
+    }
+
+    #[test]
+    fn copying_a_layer_overwrites_the_destination_only() {
+        let original = numbered(4, 3);
+        let mut map = original.clone();
+        map.copy_layer(0, 1);
+
+        assert_eq!(cells(&map, 1), cells(&original, 0));
+        assert_eq!(cells(&map, 0), cells(&original, 0));
+        assert_eq!(map.layer_name(1), original.layer_name(1));
+
+        // copying from or to a missing layer does nothing
+        map.copy_layer(5, 0);
+        map.copy_layer(1, 5);
+        assert_eq!(cells(&map, 0), cells(&original, 0));
+        assert_eq!(map.layer_count(), 2);
+    }
+
+    #[test]
+    fn swapping_layers_exchanges_their_contents() {
+        let original = numbered(4, 3);
+        let mut map = original.clone();
+        map.swap_layers(0, 1);
+
+        assert_eq!(cells(&map, 0), cells(&original, 1));
+        assert_eq!(cells(&map, 1), cells(&original, 0));
+        assert_eq!(map.layer_name(0), original.layer_name(1));
+
+        map.swap_layers(1, 0);
+        assert_eq!(map, original);
+    }
+
+    #[test]
+    fn cropping_keeps_the_content_in_the_middle() {
+        let mut map = TileMap::new(6, 5);
+        assert_eq!(map.bounding_box(), None);
+        assert!(!map.crop_to_content(), "an empty map is left alone");
+        assert_eq!(map.get_dimensions(), (6, 5));
+
+        map.set_tile(2, 1, Some(number(2, 1, 0)), 0);
+        map.set_tile(4, 3, Some(number(4, 3, 1)), 1);
+        assert_eq!(map.bounding_box(), Some((2, 1, 3, 3)));
+
+        assert!(map.crop_to_content());
+        assert_eq!(map.get_dimensions(), (3, 3));
+        assert_eq!(map.get_layer_tile(0, 0, 0), Some(number(2, 1, 0)));
+        assert_eq!(map.get_layer_tile(2, 2, 1), Some(number(4, 3, 1)));
+        assert_eq!(
+            cells(&map, 0)
+                .iter()
+                .chain(&cells(&map, 1))
+                .flatten()
+                .count(),
+            2
+        );
+
+        assert!(!map.crop_to_content(), "nothing is left to crop");
+    }
+
+    /// Checks that every layer has one tile per cell of the map
+    fn assert_consistent(map: &TileMap, operation: &str) {
+        let cells = map.width as usize * map.height as usize;
+        for layer in &map.layers {
+            assert_eq!(
+                layer.tiles.len(),
+                cells,
+                "{:?} after {}",
+                layer.name,
+                operation
+            );
+        }
+    }
+
+    #[test]
+    fn layers_keep_the_dimensions_of_the_map() {
+        let mut map = numbered(5, 3);
+        assert_consistent(&map, "creating it");
+
+        map.add_layer(1, "Middle".to_string());
+        assert_consistent(&map, "adding a layer");
+        map.swap_layers(0, 2);
+        map.copy_layer(0, 1);
+        map.remove_layer(1);
+        assert_consistent(&map, "rearranging the layers");
+
+        map.offset_layer(2, -1, true, 0);
+        map.fill_layer(Some(number(0, 0, 0)), 1);
+        map.clear_layer(0);
+        map.replace_tile((0, 0), (0, 1), None);
+        assert_consistent(&map, "editing a layer");
+
+        for transform in MapTransform::ALL {
+            map.transform(transform);
+            assert_consistent(&map, &transform.to_string());
+        }
+
+        map.resize(7, 2);
+        assert_consistent(&map, "resizing it");
+        map.clear_layer(1);
+        map.set_tile(3, 1, Some(number(3, 1, 0)), 1);
+        map.crop_to_content();
+        assert_eq!(map.get_dimensions(), (1, 1));
+        assert_consistent(&map, "cropping it");
+    }
+
+    #[test]
+    fn tiles_are_stored_row_after_row() {
+        let mut map = numbered(4, 3);
+
+        // the one of (x, y) comes after the whole rows above it
+        let tiles = &map.layers[0].tiles;
+        assert_eq!(tiles[2 * 4 + 1], Some(number(1, 2, 0)));
+        assert_eq!(tiles[4], Some(number(0, 1, 0)));
+        assert_eq!(map.get_layer_tile(3, 2, 0), Some(number(3, 2, 0)));
+
+        map.set_tile(3, 1, None, 0);
+        assert_eq!(map.layers[0].tiles[4 + 3], None);
+        assert_eq!(map.get_layer_tile(3, 1, 0), None);
+        map.set_tile(3, 1, Some(number(3, 1, 0)), 0);
+
+        // cells keep their coordinates, the new ones being empty
+        map.resize(6, 2);
+        for y in 0..2 {
+            for x in 0..6 {
+                let expected = (x < 4).then(|| number(x, y, 0));
+                assert_eq!(map.get_layer_tile(x, y, 0), expected, "({x}, {y})");
+            }
+        }
+
+        map.resize(3, 4);
+        for y in 0..4 {
+            for x in 0..3 {
+                let expected = (y < 2).then(|| number(x, y, 1));
+                assert_eq!(map.get_layer_tile(x, y, 1), expected, "({x}, {y})");
+            }
+        }
+        assert_eq!(map.get_layer_tile(3, 0, 1), None);
+    }
+
+    #[test]
+    fn diff_lists_the_cells_differing() {
+        let map = numbered(4, 3);
+        let mut other = map.clone();
+        other.set_tile(1, 2, None, 0);
+        other.set_tile(3, 0, Some(number(3, 0, 0).flipped_horizontally()), 1);
+        assert_eq!(map.diff(&map), vec![]);
+
+        let mut diff = map.diff(&other);
+        diff.sort();
+        assert_eq!(diff, vec![(1, 2, 0), (3, 0, 1)]);
+
+        // cells missing from a map are empty in it
+        let mut larger = TileMap::with_layers(5, 3, ["Background".to_string()]);
+        larger.set_tile(0, 0, Some(number(0, 0, 0)), 0);
+        larger.set_tile(4, 1, Some(number(4, 1, 0)), 0);
+        let compared = numbered(1, 1);
+        let mut diff = larger.diff(&compared);
+        diff.sort();
+        assert_eq!(diff, vec![(0, 0, 1), (4, 1, 0)]);
+    }
+
+    #[test]
+    fn replacing_moves_tiles_between_sheets() {
+        let tile = Tile::new(5, true, false);
+        let mut map = TileMap::new(2, 1);
+        map.set_tile(0, 0, Some(tile), 0);
+        map.set_tile(1, 0, Some(Tile { sheet: 1, ..tile }), 0);
+
+        map.replace_tile((1, 5), (0, 7), None);
+        assert_eq!(map.get_layer_tile(0, 0, 0), Some(tile));
+        assert_eq!(map.get_layer_tile(1, 0, 0), Some(Tile::new(7, true, false)));
+    }
+
+    #[test]
+    fn tiles_are_counted_by_sheet() {
+        let tile = Tile::new(3, false, false);
+        let mut map = TileMap::new(3, 1);
+        map.add_layer(1, "Top".to_string());
+        map.set_tile(0, 0, Some(tile), 0);
+        map.set_tile(1, 0, Some(Tile { sheet: 1, ..tile }), 0);
+        map.set_tile(2, 0, Some(Tile::new(3, true, false)), 1);
+
+        let counts = map.tile_counts(None);
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&(0, 3)], 2);
+        assert_eq!(counts[&(1, 3)], 1);
+
+        let counts = map.tile_counts(Some(0));
+        assert_eq!(counts[&(0, 3)], 1);
+        assert_eq!(counts[&(1, 3)], 1);
     }
 }
@@ -1,92 +1,511 @@
+use std::{collections::VecDeque, ops::Range};
+
+use asefile::AsepriteFile;
 use iced::{
-    canvas::{event::Status, Event},
-    mouse,
+    canvas::{event::Status, Event, Text},
+    keyboard, mouse,
     pure::{
         widget::{canvas, Canvas},
         Element,
     },
-    Color, Length, Point, Size, Vector,
+    Color, Length, Point, Rectangle, Size, Vector,
 };
 
-use crate::{Message, Tiles};
+use crate::{tileimage::TileImages, tilemap::Tile, Message, Tiles};
 
-const TILES_PER_LINE: u32 = 5;
+const DEFAULT_TILES_PER_LINE: u32 = 5;
+const MAX_TILES_PER_LINE: u32 = 16;
 const SCALE_FACTOR: u32 = 4;
+/// Height of the label above each group of tiles
+const HEADER_HEIGHT: f32 = 20.0;
+/// Size of the index drawn over the tiles
+const INDEX_SIZE: f32 = 12.0;
+/// Number of tiles kept in the strip of the last ones selected
+const RECENT_TILES: usize = 6;
+
+/// Tiles of a block, with their position in it
+pub type TileBlock = Vec<(u16, u16, Tile)>;
 
 pub struct TileSelector {
+    pub tile_size: u16,
+    /// Number of frames on each line of the grid
+    tiles_per_line: u32,
+    /// Index of the tilesheet shown
+    sheet: u8,
     selected: Option<u32>,
+    /// Tag of the selected animation, its first frame being `selected`
+    selected_anim: Option<u32>,
+    /// Tiles the scatter brush picks from, the selected one included
+    brush_set: Vec<u32>,
+    /// Last tiles selected, the latest first, each only once
+    recent_tiles: VecDeque<u32>,
+    /// Block of frames dragged over, as `(x, y, width, height)` in the grid of the group of
+    /// `selected`, which is its top left frame
+    selection: Option<(u32, u32, u32, u32)>,
+    filter: Option<String>,
+    /// Whether the index of each frame is drawn over it
+    show_indices: bool,
+    /// Frames of the sheet, grouped by Aseprite tag
+    groups: Vec<(String, Range<u32>, Option<u32>)>,
+    /// Labels of the groups shown, with their vertical position and tag
+    headers: Vec<(String, f32, Option<u32>)>,
+    /// Frames shown, with the top left corner of their cell
+    visible_tiles: Vec<(u32, Point)>,
+    height: f32,
     content: Tiles,
     cache: canvas::Cache,
+    images: TileImages,
 }
 
 impl TileSelector {
     pub fn new(tiles: Tiles) -> Self {
         TileSelector {
+            tile_size: 8,
+            tiles_per_line: DEFAULT_TILES_PER_LINE,
+            sheet: 0,
             selected: None,
+            selected_anim: None,
+            brush_set: Vec::new(),
+            recent_tiles: VecDeque::new(),
+            selection: None,
+            filter: None,
+            show_indices: false,
+            groups: Vec::new(),
+            headers: Vec::new(),
+            visible_tiles: Vec::new(),
+            height: 0.0,
             content: tiles,
             cache: Default::default(),
+            images: Default::default(),
         }
     }
 
     pub fn view(&self) -> Element<'_, crate::Message> {
+        let cell = self.tile_size as u32 + 1;
+
         Canvas::new(self)
             .width(Length::Units(
-                ((1 + 9 * TILES_PER_LINE) * SCALE_FACTOR)
+                ((1 + cell * self.tiles_per_line) * SCALE_FACTOR)
                     .try_into()
                     .unwrap(),
             ))
-            .height(Length::Units(match &*self.content.borrow() {
-                Some(content) => {
-                    ((((content.num_frames() as f32 / TILES_PER_LINE as f32).ceil() as u32) * 9
-                        + 1)
-                        * SCALE_FACTOR)
-                        .try_into()
-                        .unwrap()
-                }
-                None => 0,
-            }))
+            .height(Length::Units(self.height.ceil() as u16))
+            .into()
+    }
+
+    /// Small view of `brush`, flipped and rotated as it will be painted
+    pub fn brush_preview(&self, brush: Option<Tile>) -> Element<'_, Message> {
+        let side = (self.tile_size as u32 * SCALE_FACTOR) as u16;
+
+        Canvas::new(BrushPreview {
+            selector: self,
+            brush,
+        })
+        .width(Length::Units(side))
+        .height(Length::Units(side))
+        .into()
+    }
+
+    /// Strip of the last tiles selected, `width` pixels wide, clicking one selecting it again
+    pub fn recent_view(&self, width: u16) -> Element<'_, Message> {
+        let height = if self.recent_tiles.is_empty() {
+            0
+        } else {
+            recent_side(self.tile_size, width as f32) as u16
+        };
+
+        Canvas::new(RecentTiles { selector: self })
+            .width(Length::Units(width))
+            .height(Length::Units(height))
             .into()
     }
 
+    pub fn set_show_indices(&mut self, show: bool) {
+        self.show_indices = show;
+        self.cache.clear();
+    }
+
     pub fn select(&mut self, i: u32) {
-        match &*self.content.borrow() {
-            Some(content) => {
-                if i < content.num_frames() {
-                    self.selected = Some(i);
-                    self.cache.clear();
+        if let Some(content) = self
+            .content
+            .borrow()
+            .get(self.sheet as usize)
+            .map(|s| &s.content)
+        {
+            if i < content.num_frames() {
+                self.selected = Some(i);
+                self.selected_anim = None;
+                self.brush_set = vec![i];
+                self.selection = None;
+                self.cache.clear();
+
+                self.recent_tiles.retain(|tile| *tile != i);
+                self.recent_tiles.push_front(i);
+                self.recent_tiles.truncate(RECENT_TILES);
+            }
+        }
+    }
+
+    /// Selects the block of frames between the corners `from` and `to`, which must be in the
+    /// same group
+    pub fn select_block(&mut self, from: u32, to: u32) {
+        let frames = match self.group_of(from) {
+            Some(frames) if frames.contains(&to) => frames,
+            _ => return,
+        };
+
+        let grid = |i: u32| {
+            let position = i - frames.start;
+            (
+                position % self.tiles_per_line,
+                position / self.tiles_per_line,
+            )
+        };
+        let ((from_x, from_y), (to_x, to_y)) = (grid(from), grid(to));
+        let (x, y) = (from_x.min(to_x), from_y.min(to_y));
+        let first = frames.start + x + y * self.tiles_per_line;
+
+        self.selected = Some(first);
+        self.selected_anim = None;
+        self.brush_set = vec![first];
+        self.selection = Some((x, y, from_x.abs_diff(to_x) + 1, from_y.abs_diff(to_y) + 1));
+        self.cache.clear();
+    }
+
+    /// Whether the selected block still starts at the selected frame, with its opposite corners
+    /// in the same group
+    fn block_in_group(&self) -> bool {
+        let ((x, y, width, height), selected) = match (self.selection, self.selected) {
+            (Some(selection), Some(selected)) => (selection, selected),
+            _ => return false,
+        };
+        let frames = match self.group_of(selected) {
+            Some(frames) => frames,
+            None => return false,
+        };
+
+        let frame = |dx: u32, dy: u32| frames.start + x + dx + (y + dy) * self.tiles_per_line;
+        frame(0, 0) == selected
+            && frames.contains(&frame(width - 1, 0))
+            && frames.contains(&frame(0, height - 1))
+    }
+
+    /// Frames of the group `i` is in
+    fn group_of(&self, i: u32) -> Option<Range<u32>> {
+        self.groups
+            .iter()
+            .map(|(_, frames, _)| frames.clone())
+            .find(|frames| frames.contains(&i))
+    }
+
+    /// Adds `i` to the brush set, or removes it if it already is in
+    pub fn toggle(&mut self, i: u32) {
+        if self.selected_anim.is_some() {
+            return self.select(i);
+        }
+
+        match self.brush_set.iter().position(|tile| *tile == i) {
+            Some(position) => {
+                self.brush_set.remove(position);
+                if self.selected == Some(i) {
+                    self.selected = self.brush_set.last().copied();
                 }
+                self.selection = None;
+                self.cache.clear();
+            }
+            None => {
+                self.selected = Some(i);
+                self.brush_set.push(i);
+                self.selection = None;
+                self.cache.clear();
+            }
+        }
+    }
+
+    pub fn brush_set(&self) -> &[u32] {
+        &self.brush_set
+    }
+
+    /// Selects the animation of the tag `tag`
+    pub fn select_animation(&mut self, tag: u32) {
+        if let Some(content) = self
+            .content
+            .borrow()
+            .get(self.sheet as usize)
+            .map(|s| &s.content)
+        {
+            if tag < content.num_tags() {
+                self.selected = Some(content.tag(tag).from_frame());
+                self.selected_anim = Some(tag);
+                self.brush_set.clear();
+                self.selection = None;
+                self.cache.clear();
             }
-            None => {}
         }
     }
 
     pub fn unselect(&mut self) {
         self.selected = None;
+        self.selected_anim = None;
+        self.brush_set.clear();
+        self.selection = None;
         self.cache.clear();
     }
 
-    pub fn reset(&mut self) {
-        self.selected = None;
-        self.cache.clear();
+    /// Reads the shown sheet again, with `keep_selection` the selected tiles still in it
+    /// staying selected
+    pub fn reset(&mut self, keep_selection: bool) {
+        let (frames, tags) = match self.content.borrow().get(self.sheet as usize) {
+            Some(sheet) => {
+                self.groups = read_groups(&sheet.content);
+                (sheet.content.num_frames(), sheet.content.num_tags())
+            }
+            None => {
+                self.groups = Vec::new();
+                (0, 0)
+            }
+        };
+
+        if keep_selection {
+            // the first frame of an animation was selected with it only
+            if self.selected_anim.is_some_and(|tag| tag >= tags) {
+                self.selected = None;
+                self.selected_anim = None;
+            }
+            self.selected = self.selected.filter(|i| *i < frames);
+            self.brush_set.retain(|i| *i < frames);
+            self.recent_tiles.retain(|i| *i < frames);
+            if !self.block_in_group() {
+                self.selection = None;
+            }
+        } else {
+            self.selected = None;
+            self.selected_anim = None;
+            self.brush_set.clear();
+            // the tiles were those of another sheet
+            self.recent_tiles.clear();
+            self.selection = None;
+        }
+
+        self.images.clear();
+        self.update_layout();
         // do NOT reset reference to tiles, otherwise it’s lost forever !
     }
 
+    pub fn sheet(&self) -> u8 {
+        self.sheet
+    }
+
+    /// Shows the tilesheet `sheet`, unselecting the current tile
+    pub fn select_sheet(&mut self, sheet: u8) {
+        self.sheet = sheet;
+        self.reset(false);
+    }
+
+    pub fn tiles_per_line(&self) -> u32 {
+        self.tiles_per_line
+    }
+
+    /// Lays the frames out on `tiles_per_line` columns, which loses the block selected
+    pub fn set_tiles_per_line(&mut self, tiles_per_line: u32) {
+        self.tiles_per_line = tiles_per_line.clamp(1, MAX_TILES_PER_LINE);
+        self.selection = None;
+        self.update_layout();
+    }
+
+    pub fn show_indices(&self) -> bool {
+        self.show_indices
+    }
+
+    /// Number of tiles of the shown sheet
+    pub fn tile_count(&self) -> u32 {
+        self.content
+            .borrow()
+            .get(self.sheet as usize)
+            .map_or(0, |sheet| sheet.content.num_frames())
+    }
+
+    /// Selects `i`, clearing the filter if it hides the group of the tile
+    pub fn select_and_reveal(&mut self, i: u32) {
+        self.select(i);
+        if self.selected == Some(i) && !self.visible_tiles.iter().any(|(tile, _)| *tile == i) {
+            self.set_filter(String::new());
+        }
+    }
+
+    pub fn filter(&self) -> &str {
+        self.filter.as_deref().unwrap_or_default()
+    }
+
+    /// Only show the groups with a name containing `filter`, or every group if it is empty
+    pub fn set_filter(&mut self, filter: String) {
+        self.filter = if filter.is_empty() {
+            None
+        } else {
+            Some(filter)
+        };
+        self.update_layout();
+    }
+
+    /// Places the visible groups one below the other, each with its header
+    fn update_layout(&mut self) {
+        let cell_side = ((self.tile_size as u32 + 1) * SCALE_FACTOR) as f32;
+        let filter = self.filter.as_deref().map(str::to_lowercase);
+
+        self.headers.clear();
+        self.visible_tiles.clear();
+        let mut y = 0.0;
+
+        for (name, frames, tag) in &self.groups {
+            if let Some(filter) = &filter {
+                if !name.to_lowercase().contains(filter) {
+                    continue;
+                }
+            }
+
+            self.headers.push((name.clone(), y, *tag));
+            y += HEADER_HEIGHT;
+
+            for (position, i) in frames.clone().enumerate() {
+                let position = position as u32;
+                self.visible_tiles.push((
+                    i,
+                    Point::new(
+                        (position % self.tiles_per_line) as f32 * cell_side,
+                        y + (position / self.tiles_per_line) as f32 * cell_side,
+                    ),
+                ));
+            }
+
+            let lines = (frames.len() as f32 / self.tiles_per_line as f32).ceil();
+            y += lines * cell_side;
+        }
+
+        // room for the border of the last line
+        self.height = if self.headers.is_empty() {
+            0.0
+        } else {
+            y + SCALE_FACTOR as f32
+        };
+        self.cache.clear();
+    }
+
     pub fn get_selected(&self) -> Option<u32> {
         self.selected
     }
+
+    /// Selected frame or animation, neither flipped nor rotated
+    pub fn get_selected_tile(&self) -> Option<Tile> {
+        self.selected.map(|value| Tile {
+            sheet: self.sheet,
+            anim: self.selected_anim,
+            ..Tile::new(value, false, false)
+        })
+    }
+
+    /// Dimensions of the selected block and its frames with their position in it, `None`
+    /// unless several frames are selected
+    pub fn get_selected_block(&self) -> Option<(u16, u16, TileBlock)> {
+        let (x, y, width, height) = self.selection?;
+        let frames = self.group_of(self.selected?)?;
+
+        let mut block = Vec::new();
+        for dy in 0..height {
+            for dx in 0..width {
+                let value = frames.start + x + dx + (y + dy) * self.tiles_per_line;
+                // the last line of a group may be incomplete
+                if frames.contains(&value) {
+                    let tile = Tile {
+                        sheet: self.sheet,
+                        ..Tile::new(value, false, false)
+                    };
+                    block.push((dx as u16, dy as u16, tile));
+                }
+            }
+        }
+
+        Some((width as u16, height as u16, block))
+    }
+
+    /// Visible frame under `position`
+    fn tile_at(&self, position: Point) -> Option<u32> {
+        let cell_side = ((self.tile_size as u32 + 1) * SCALE_FACTOR) as f32;
+
+        self.visible_tiles
+            .iter()
+            .find(|(_, origin)| {
+                (origin.x..origin.x + cell_side).contains(&position.x)
+                    && (origin.y..origin.y + cell_side).contains(&position.y)
+            })
+            .map(|(i, _)| *i)
+    }
+}
+
+#[derive(Default)]
+pub struct SelectorState {
+    modifiers: keyboard::Modifiers,
+    /// Frame the drag started on, and the one last hovered
+    drag: Option<(u32, u32)>,
+}
+
+/// Groups frames by tag, in frame order, frames without a tag being put in "Untagged" groups
+fn read_groups(content: &AsepriteFile) -> Vec<(String, Range<u32>, Option<u32>)> {
+    let mut groups: Vec<(String, Range<u32>, Option<u32>)> = (0..content.num_tags())
+        .map(|id| {
+            let tag = content.tag(id);
+            (
+                tag.name().to_string(),
+                tag.from_frame()..tag.to_frame() + 1,
+                Some(id),
+            )
+        })
+        .collect();
+
+    let mut untagged_start = None;
+    for i in 0..=content.num_frames() {
+        let untagged =
+            i < content.num_frames() && !groups.iter().any(|(_, frames, _)| frames.contains(&i));
+
+        match (untagged, untagged_start) {
+            (true, None) => untagged_start = Some(i),
+            (false, Some(start)) => {
+                groups.push(("Untagged".to_string(), start..i, None));
+                untagged_start = None;
+            }
+            _ => (),
+        }
+    }
+
+    groups.sort_by_key(|(_, frames, _)| frames.start);
+    groups
 }
 
 impl canvas::Program<Message> for TileSelector {
-    type State = ();
+    type State = SelectorState;
 
     fn update(
         &self,
-        _state: &mut Self::State,
+        state: &mut Self::State,
         event: iced::canvas::Event,
         bounds: iced::Rectangle,
         cursor: iced::canvas::Cursor,
     ) -> (iced::canvas::event::Status, Option<Message>) {
-        if self.content.borrow().is_none() {
+        if let Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) = event {
+            state.modifiers = modifiers;
+            return (Status::Ignored, None);
+        }
+
+        if let Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) = event {
+            state.drag = None;
+            return (Status::Ignored, None);
+        }
+
+        // never captured, for the scrollable around the selector to scroll it
+        if let Event::Mouse(mouse::Event::WheelScrolled { .. }) = event {
+            return (Status::Ignored, None);
+        }
+
+        if self.content.borrow().get(self.sheet as usize).is_none() {
             return (Status::Ignored, None);
         }
 
@@ -97,27 +516,66 @@ impl canvas::Program<Message> for TileSelector {
         };
 
         match event {
-            Event::Mouse(mouse_event) => match mouse_event {
-                mouse::Event::ButtonPressed(button) => match button {
-                    mouse::Button::Left => {
-                        let x_tile = cursor_position.x.round() as u32 / (9 * SCALE_FACTOR);
-                        let y_tile = cursor_position.y.round() as u32 / (9 * SCALE_FACTOR);
-
-                        let pressed = x_tile + y_tile * TILES_PER_LINE;
-
-                        if let Some(current) = self.selected {
-                            if current == pressed {
-                                // same, ignore
-                                return (Status::Captured, None);
+            Event::Mouse(mouse::Event::ButtonPressed(button)) => match button {
+                mouse::Button::Left => {
+                    // a header selects the animation of its tag
+                    if let Some((_, _, tag)) = self
+                        .headers
+                        .iter()
+                        .find(|(_, y, _)| (*y..*y + HEADER_HEIGHT).contains(&cursor_position.y))
+                    {
+                        return match tag {
+                            Some(tag) if self.selected_anim != Some(*tag) => {
+                                (Status::Captured, Some(Message::AnimationSelected(*tag)))
                             }
+                            _ => (Status::Captured, None),
+                        };
+                    }
+
+                    let pressed = match self.tile_at(cursor_position) {
+                        Some(pressed) => pressed,
+                        None => return (Status::Captured, None),
+                    };
+
+                    // with Ctrl, tiles are added to the brush set
+                    if state.modifiers.command() {
+                        return (Status::Captured, Some(Message::TileToggled(pressed)));
+                    }
+
+                    state.drag = Some((pressed, pressed));
+
+                    if let (Some(current), None, None) =
+                        (self.selected, self.selected_anim, self.selection)
+                    {
+                        if current == pressed && self.brush_set.len() == 1 {
+                            // same, ignore
+                            return (Status::Captured, None);
                         }
-                        (Status::Captured, Some(Message::TileSelected(pressed)))
                     }
-                    mouse::Button::Right => (Status::Captured, Some(Message::TileUnSelected)),
-                    _ => (Status::Ignored, None),
-                },
+                    (Status::Captured, Some(Message::TileSelected(pressed)))
+                }
+                mouse::Button::Right => (Status::Captured, Some(Message::TileUnSelected)),
                 _ => (Status::Ignored, None),
             },
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                let (start, last) = match state.drag {
+                    Some(drag) => drag,
+                    None => return (Status::Ignored, None),
+                };
+
+                match self.tile_at(cursor_position) {
+                    Some(hovered) if hovered != last => {
+                        state.drag = Some((start, hovered));
+                        let message = if hovered == start {
+                            Message::TileSelected(start)
+                        } else {
+                            Message::BlockSelected(start, hovered)
+                        };
+                        (Status::Captured, Some(message))
+                    }
+                    _ => (Status::Captured, None),
+                }
+            }
             _ => (Status::Ignored, None),
         }
     }
@@ -129,80 +587,79 @@ impl canvas::Program<Message> for TileSelector {
         _cursor: iced::canvas::Cursor,
     ) -> Vec<iced::canvas::Geometry> {
         let selector = self.cache.draw(bounds.size(), |frame| {
-            if let Some(content) = &*self.content.borrow() {
-                // for each tile
-                for i in 0..content.num_frames() {
-                    // for each pixel in the tile
-
-                    if let Some(selected) = self.selected {
-                        if selected == i {
-                            frame.with_save(|frame| {
-                                frame.translate(Vector::new(
-                                    (9 * (i % TILES_PER_LINE) * SCALE_FACTOR) as f32,
-                                    (9 * (i / TILES_PER_LINE) * SCALE_FACTOR) as f32,
-                                ));
-
-                                let fill = Color::new(1.0, 0.0, 0.0, 0.7);
-
-                                // top
-                                frame.fill_rectangle(
-                                    Point { x: 0.0, y: 0.0 },
-                                    Size::new((10 * SCALE_FACTOR) as f32, SCALE_FACTOR as f32),
-                                    fill,
-                                );
-
-                                // left
-                                frame.fill_rectangle(
-                                    Point { x: 0.0, y: 0.0 },
-                                    Size::new(SCALE_FACTOR as f32, (10 * SCALE_FACTOR) as f32),
-                                    fill,
-                                );
-
-                                // down
-                                frame.fill_rectangle(
-                                    Point {
-                                        x: 0.0,
-                                        y: (9 * SCALE_FACTOR) as f32,
-                                    },
-                                    Size::new((10 * SCALE_FACTOR) as f32, SCALE_FACTOR as f32),
-                                    fill,
-                                );
-
-                                // right
-                                frame.fill_rectangle(
-                                    Point {
-                                        x: (9 * SCALE_FACTOR) as f32,
-                                        y: 0.0,
-                                    },
-                                    Size::new(SCALE_FACTOR as f32, (10 * SCALE_FACTOR) as f32),
-                                    fill,
-                                );
-                            });
-                        }
+            let cell = self.tile_size as u32 + 1;
+
+            if let Some(content) = self
+                .content
+                .borrow()
+                .get(self.sheet as usize)
+                .map(|s| &s.content)
+            {
+                for (name, y, tag) in &self.headers {
+                    frame.fill_text(Text {
+                        content: name.clone(),
+                        position: Point::new(SCALE_FACTOR as f32, *y + 2.0),
+                        color: if tag.is_some() && *tag == self.selected_anim {
+                            Color::from_rgb(1.0, 0.0, 0.0)
+                        } else {
+                            Color::BLACK
+                        },
+                        size: HEADER_HEIGHT - 4.0,
+                        ..Default::default()
+                    });
+                }
+
+                for &(i, origin) in &self.visible_tiles {
+                    let highlight = if self.selected_anim.is_some() || self.selection.is_some() {
+                        None
+                    } else if self.selected == Some(i) {
+                        Some(Color::new(1.0, 0.0, 0.0, 0.7))
+                    } else if self.brush_set.contains(&i) {
+                        Some(Color::new(1.0, 0.6, 0.0, 0.7))
+                    } else {
+                        None
+                    };
+
+                    if let Some(fill) = highlight {
+                        outline(frame, origin, (cell, cell), fill);
                     }
 
-                    for (idx, pixel) in content.frame(i).image().pixels().take(64).enumerate() {
-                        frame.with_save(|frame| {
-                            // move at pixel location
-
-                            frame.translate(Vector::new(
-                                ((9 * (i % TILES_PER_LINE) + 1 + (idx as u32 % 8)) * SCALE_FACTOR)
-                                    as f32,
-                                ((9 * (i / TILES_PER_LINE) + 1 + (idx as u32 / 8)) * SCALE_FACTOR)
-                                    as f32,
-                            ));
-
-                            frame.fill_rectangle(
-                                Point::new(0 as f32, 0 as f32),
-                                Size::new(SCALE_FACTOR as f32, SCALE_FACTOR as f32),
-                                Color::new(
-                                    pixel.0[0] as f32 / 255.0,
-                                    pixel.0[1] as f32 / 255.0,
-                                    pixel.0[2] as f32 / 255.0,
-                                    pixel.0[3] as f32 / 255.0,
-                                ),
-                            )
-                        })
+                    let tile = Tile::new(i, false, false);
+                    let image = self.images.get(content, tile, self.tile_size);
+                    image.draw(
+                        frame,
+                        origin + Vector::new(SCALE_FACTOR as f32, SCALE_FACTOR as f32),
+                        SCALE_FACTOR as f32,
+                        1.0,
+                    );
+
+                    // unreadable on cells smaller than two lines of text
+                    if self.show_indices && (cell * SCALE_FACTOR) as f32 >= 2.0 * INDEX_SIZE {
+                        frame.fill_text(Text {
+                            content: i.to_string(),
+                            position: Point::new(
+                                origin.x + SCALE_FACTOR as f32,
+                                origin.y + SCALE_FACTOR as f32,
+                            ),
+                            color: Color::WHITE,
+                            size: INDEX_SIZE,
+                            ..Default::default()
+                        });
+                    }
+                }
+
+                if let (Some((_, _, width, height)), Some(selected)) =
+                    (self.selection, self.selected)
+                {
+                    if let Some((_, origin)) =
+                        self.visible_tiles.iter().find(|(i, _)| *i == selected)
+                    {
+                        outline(
+                            frame,
+                            *origin,
+                            (width * cell, height * cell),
+                            Color::new(1.0, 0.0, 0.0, 0.7),
+                        );
                     }
                 }
             }
@@ -211,3 +668,197 @@ impl canvas::Program<Message> for TileSelector {
         vec![selector]
     }
 }
+
+struct BrushPreview<'a> {
+    selector: &'a TileSelector,
+    brush: Option<Tile>,
+}
+
+impl canvas::Program<Message> for BrushPreview<'_> {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        bounds: iced::Rectangle,
+        _cursor: iced::canvas::Cursor,
+    ) -> Vec<iced::canvas::Geometry> {
+        let mut frame = canvas::Frame::new(bounds.size());
+
+        if let Some(brush) = self.brush {
+            let sheets = self.selector.content.borrow();
+            if let Some(sheet) = sheets.get(brush.sheet as usize) {
+                if brush.value < sheet.content.num_frames() {
+                    let image =
+                        self.selector
+                            .images
+                            .get(&sheet.content, brush, self.selector.tile_size);
+                    image.draw(&mut frame, Point::ORIGIN, SCALE_FACTOR as f32, 1.0);
+                }
+            }
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Side of the cells of the strip of recent tiles, as large as in the selector if they fit in
+/// `width`
+fn recent_side(tile_size: u16, width: f32) -> f32 {
+    let side = (tile_size as u32 * SCALE_FACTOR) as f32 + 2.0 * SCALE_FACTOR as f32;
+    side.min(width / RECENT_TILES as f32).floor()
+}
+
+struct RecentTiles<'a> {
+    selector: &'a TileSelector,
+}
+
+impl canvas::Program<Message> for RecentTiles<'_> {
+    type State = ();
+
+    fn update(
+        &self,
+        _state: &mut Self::State,
+        event: Event,
+        bounds: Rectangle,
+        cursor: iced::canvas::Cursor,
+    ) -> (Status, Option<Message>) {
+        let position = match (event, cursor.position_in(&bounds)) {
+            (Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)), Some(position)) => {
+                position
+            }
+            _ => return (Status::Ignored, None),
+        };
+
+        let side = recent_side(self.selector.tile_size, bounds.width);
+        match self.selector.recent_tiles.get((position.x / side) as usize) {
+            Some(&i) => (Status::Captured, Some(Message::TileSelected(i))),
+            None => (Status::Captured, None),
+        }
+    }
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        bounds: Rectangle,
+        _cursor: iced::canvas::Cursor,
+    ) -> Vec<iced::canvas::Geometry> {
+        let mut frame = canvas::Frame::new(bounds.size());
+        let side = recent_side(self.selector.tile_size, bounds.width);
+        // a border of a pixel of the selector around each tile, for the highlight
+        let border = side / (self.selector.tile_size as f32 + 2.0);
+
+        let sheets = self.selector.content.borrow();
+        if let Some(sheet) = sheets.get(self.selector.sheet as usize) {
+            for (position, &i) in self.selector.recent_tiles.iter().enumerate() {
+                let origin = Point::new(position as f32 * side, 0.0);
+                if self.selector.selected == Some(i) && self.selector.selected_anim.is_none() {
+                    frame.fill_rectangle(
+                        origin,
+                        Size::new(side, side),
+                        Color::new(1.0, 0.0, 0.0, 0.7),
+                    );
+                }
+
+                let tile = Tile::new(i, false, false);
+                let image = self
+                    .selector
+                    .images
+                    .get(&sheet.content, tile, self.selector.tile_size);
+                image.draw(
+                    &mut frame,
+                    origin + Vector::new(border, border),
+                    border,
+                    1.0,
+                );
+            }
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Draws a border around the cells starting at `origin`, their size being in unscaled pixels
+fn outline(frame: &mut canvas::Frame, origin: Point, (width, height): (u32, u32), fill: Color) {
+    frame.with_save(|frame| {
+        frame.translate(Vector::new(origin.x, origin.y));
+
+        // top
+        frame.fill_rectangle(
+            Point { x: 0.0, y: 0.0 },
+            Size::new(((width + 1) * SCALE_FACTOR) as f32, SCALE_FACTOR as f32),
+            fill,
+        );
+
+        // left
+        frame.fill_rectangle(
+            Point { x: 0.0, y: 0.0 },
+            Size::new(SCALE_FACTOR as f32, ((height + 1) * SCALE_FACTOR) as f32),
+            fill,
+        );
+
+        // down
+        frame.fill_rectangle(
+            Point {
+                x: 0.0,
+                y: (height * SCALE_FACTOR) as f32,
+            },
+            Size::new(((width + 1) * SCALE_FACTOR) as f32, SCALE_FACTOR as f32),
+            fill,
+        );
+
+        // right
+        frame.fill_rectangle(
+            Point {
+                x: (width * SCALE_FACTOR) as f32,
+                y: 0.0,
+            },
+            Size::new(SCALE_FACTOR as f32, ((height + 1) * SCALE_FACTOR) as f32),
+            fill,
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, path::Path, rc::Rc};
+
+    use super::*;
+    use crate::TileSheet;
+
+    fn sheet(file: &str) -> TileSheet {
+        let file = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join(file);
+        TileSheet {
+            name: String::new(),
+            content: AsepriteFile::read_file(&file).unwrap(),
+            file,
+        }
+    }
+
+    #[test]
+    fn reloading_drops_the_selection_lost_with_the_sheet() {
+        // 4 frames, then 2
+        let tiles = Rc::new(RefCell::new(vec![sheet("tile_sheet.aseprite")]));
+        let mut selector = TileSelector::new(tiles.clone());
+        selector.reset(false);
+        selector.set_tiles_per_line(2);
+
+        selector.select_block(0, 3);
+        selector.reset(true);
+        assert_eq!(selector.get_selected(), Some(0));
+        assert!(selector.get_selected_block().is_some());
+
+        tiles.borrow_mut()[0] = sheet("large_tile_sheet.aseprite");
+        selector.reset(true);
+        assert_eq!(selector.get_selected(), Some(0));
+        assert!(selector.get_selected_block().is_none());
+
+        // the sheet has no tag left
+        selector.selected_anim = Some(0);
+        selector.reset(true);
+        assert_eq!(selector.get_selected(), None);
+        assert_eq!(selector.selected_anim, None);
+    }
+}